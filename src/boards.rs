@@ -0,0 +1,188 @@
+//! Top-row layouts of known Chromebook boards.
+//!
+//! The driver maps a Vivaldi key to F<n> by its position in the board's top
+//! row, so the same `Fn` entry sends different function keys on different
+//! boards. The layouts are data in `src/boards.txt`; see that file for the
+//! format.
+
+use crate::config::{Config, RemapCfgKey, RemapOverride, Rule, MAX_ENTRIES};
+use crate::error::{Error, Result};
+use crate::keys::parse_key;
+
+const BOARDS: &str = include_str!("boards.txt");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    pub name: String,
+    pub has_assistant_key: bool,
+    /// Top-row keys, left to right.
+    pub top_row: Vec<RemapCfgKey>,
+}
+
+impl Board {
+    /// `base` adapted to this board. Entries using `Fn` are grouped by
+    /// everything but their original key, and each group becomes one entry
+    /// per top-row key, in top-row order, where the group's first entry was.
+    /// Other entries are kept as they are. `has_assistant_key` is set from
+    /// the board rather than auto-detected. Fails if the result has more than
+    /// `MAX_ENTRIES` entries.
+    pub fn configure(&self, base: &Config) -> Result<Config> {
+        let mut templates: Vec<Rule> = Vec::new();
+        let mut entries = Vec::new();
+        for rule in &base.entries {
+            if !rule.remap_vivaldi_to_fn_keys {
+                entries.push(*rule);
+                continue;
+            }
+            let mut template = *rule;
+            template.original_key = RemapCfgKey::default();
+            if !templates.contains(&template) {
+                templates.push(template);
+                for &key in &self.top_row {
+                    entries.push(Rule { original_key: key, ..template });
+                }
+            }
+        }
+
+        if entries.len() > MAX_ENTRIES {
+            return Err(Error::TooManyEntries { count: entries.len(), max: MAX_ENTRIES });
+        }
+
+        let mut config = Config::new(base.header.clone(), entries);
+        config.header.remappings = config.entries.len() as u32;
+        config.header.has_assistant_key =
+            if self.has_assistant_key { RemapOverride::Enable } else { RemapOverride::Disable };
+        Ok(config)
+    }
+}
+
+/// Parses a board list in the `boards.txt` format.
+pub fn parse_boards(text: &str) -> Result<Vec<Board>> {
+    let mut boards = Vec::new();
+    for (i, raw_line) in text.lines().enumerate() {
+        let line = raw_line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let syntax = |message: String| Error::Syntax { line: i + 1, message };
+
+        let (name, keys) = line.split_once(':').ok_or_else(|| syntax("expected '<board>: <keys>'".to_string()))?;
+        let mut words = name.split_whitespace();
+        let name = words.next().ok_or_else(|| syntax("missing board name".to_string()))?;
+        let mut has_assistant_key = false;
+        for word in words {
+            match word {
+                "assistant" => has_assistant_key = true,
+                other => return Err(syntax(format!("unknown board option '{}'", other))),
+            }
+        }
+
+        let top_row = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(parse_key)
+            .collect::<Result<Vec<_>>>()?;
+        if top_row.is_empty() {
+            return Err(syntax(format!("board '{}' has no top-row keys", name)));
+        }
+
+        boards.push(Board { name: name.to_string(), has_assistant_key, top_row });
+    }
+    Ok(boards)
+}
+
+/// The bundled boards, in file order.
+pub fn boards() -> Vec<Board> {
+    parse_boards(BOARDS).unwrap_or_else(|e| panic!("built-in board list does not parse: {}", e))
+}
+
+/// The bundled board called `name`, ignoring case.
+pub fn board(name: &str) -> Result<Board> {
+    boards()
+        .into_iter()
+        .find(|board| board.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::UnknownBoard { name: name.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::parse_key;
+
+    fn key(name: &str) -> RemapCfgKey {
+        parse_key(name).unwrap()
+    }
+
+    #[test]
+    fn bundled_boards_parse() {
+        let boards = parse_boards(BOARDS).unwrap();
+        assert!(!boards.is_empty());
+        assert!(boards.iter().all(|board| !board.top_row.is_empty()));
+        assert_eq!(board("EVE").unwrap().name, board("eve").unwrap().name);
+        assert!(matches!(board("nonexistent"), Err(Error::UnknownBoard { .. })));
+    }
+
+    #[test]
+    fn parse_reads_options_and_comments() {
+        let boards = parse_boards("# comment\n\nfoo assistant: Back, Refresh # trailing\nbar: Mute,\n").unwrap();
+        assert_eq!(
+            boards,
+            vec![
+                Board { name: "foo".to_string(), has_assistant_key: true, top_row: vec![key("Back"), key("Refresh")] },
+                Board { name: "bar".to_string(), has_assistant_key: false, top_row: vec![key("Mute")] },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors_carry_line_numbers() {
+        for (text, line) in [("foo Back", 1), ("\nfoo turbo: Back", 2), ("foo: Back\n: Back", 2), ("foo: ,", 1)] {
+            match parse_boards(text) {
+                Err(Error::Syntax { line: actual, .. }) => assert_eq!(actual, line, "{:?}", text),
+                other => panic!("{:?}: {:?}", text, other),
+            }
+        }
+        assert!(matches!(parse_boards("foo: Nowhere"), Err(Error::InvalidKey { .. })));
+    }
+
+    #[test]
+    fn configure_expands_fn_entries_over_the_top_row() {
+        let board = Board { name: "foo".to_string(), has_assistant_key: true, top_row: vec![key("Back"), key("Mute")] };
+        let mut fn_rule = Rule::new();
+        fn_rule.original_key = key("Refresh");
+        fn_rule.remap_vivaldi_to_fn_keys = true;
+        let mut duplicate = fn_rule;
+        duplicate.original_key = key("Overview");
+        let mut other = Rule::new();
+        other.original_key = key("Lock");
+        other.remapped_key = key("Delete");
+        let base = Config::new(Default::default(), vec![fn_rule, other, duplicate]);
+
+        let config = board.configure(&base).unwrap();
+        let originals: Vec<_> = config.entries.iter().map(|rule| rule.original_key).collect();
+        assert_eq!(originals, vec![key("Back"), key("Mute"), key("Lock")]);
+        assert!(config.entries[..2].iter().all(|rule| rule.remap_vivaldi_to_fn_keys));
+        assert_eq!(config.header.remappings, 3);
+        assert_eq!(config.header.has_assistant_key, RemapOverride::Enable);
+    }
+
+    #[test]
+    fn configure_rejects_too_many_entries() {
+        let top_row: Vec<RemapCfgKey> = (0..20).map(|i| RemapCfgKey::with_values(0x10 + i, 0)).collect();
+        let board = Board { name: "wide".to_string(), has_assistant_key: false, top_row };
+        let templates: Vec<Rule> = (0..13)
+            .map(|i| {
+                let mut rule = Rule::new();
+                rule.remap_vivaldi_to_fn_keys = true;
+                rule.remapped_key = RemapCfgKey::with_values(0x10 + i, 0);
+                rule
+            })
+            .collect();
+
+        let fits = Config::new(Default::default(), templates[..12].to_vec());
+        assert_eq!(board.configure(&fits).unwrap().entries.len(), 240);
+        let base = Config::new(Default::default(), templates);
+        assert!(matches!(board.configure(&base), Err(Error::TooManyEntries { count: 260, max: MAX_ENTRIES })));
+    }
+}
@@ -0,0 +1,967 @@
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Error, Result};
+use crate::json::{ConfigFileJson, KeyStateJson, RemapOverrideJson};
+use crate::keys::{K_ASSISTANT, K_LALT, K_LCTRL, K_LSHFT, K_LWIN, K_RSHFT, KEY_BREAK, KEY_E0};
+
+// C++ multi-char literal 'CrKB' on little-endian systems stores bytes as: 42 4B 72 43
+// Which reads as "BKrC" in ASCII. We need to match this exact byte sequence.
+pub const CFG_MAGIC: u32 = u32::from_le_bytes(*b"BKrC");
+
+// enums on c and rust are not the same, hence these aliases for the raw
+// on-disk values; the owned model uses `KeyState` and `RemapOverride`
+pub type RemapCfgKeyState = i32;
+pub type RemapCfgOverride = i32;
+
+/// Condition on a modifier key for a remap entry to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(from = "KeyStateJson")]
+pub enum KeyState {
+    /// The modifier is ignored.
+    #[default]
+    NoDetect,
+    /// The modifier must be held.
+    Enforce,
+    /// The modifier must not be held.
+    EnforceNot,
+    /// A value this tool does not know, kept so it is written back unchanged.
+    /// The driver only acts on Enforce and EnforceNot, so it matches like
+    /// NoDetect.
+    Unknown(RemapCfgKeyState),
+}
+
+impl KeyState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyState::NoDetect => "NoDetect",
+            KeyState::Enforce => "Enforce",
+            KeyState::EnforceNot => "EnforceNot",
+            KeyState::Unknown(_) => "Unknown",
+        }
+    }
+
+    pub fn is_no_detect(&self) -> bool {
+        *self == KeyState::NoDetect
+    }
+}
+
+impl From<RemapCfgKeyState> for KeyState {
+    fn from(value: RemapCfgKeyState) -> Self {
+        match value {
+            0 => KeyState::NoDetect,
+            1 => KeyState::Enforce,
+            2 => KeyState::EnforceNot,
+            other => KeyState::Unknown(other),
+        }
+    }
+}
+
+impl From<KeyState> for RemapCfgKeyState {
+    fn from(state: KeyState) -> Self {
+        match state {
+            KeyState::NoDetect => 0,
+            KeyState::Enforce => 1,
+            KeyState::EnforceNot => 2,
+            KeyState::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for KeyState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyState::Unknown(value) => write!(f, "Unknown({})", value),
+            _ => f.write_str(self.as_str()),
+        }
+    }
+}
+
+/// Header override for a board feature the driver would otherwise detect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(from = "RemapOverrideJson")]
+pub enum RemapOverride {
+    #[default]
+    AutoDetect,
+    Enable,
+    Disable,
+    /// A value this tool does not know, kept so it is written back unchanged.
+    Unknown(RemapCfgOverride),
+}
+
+impl RemapOverride {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RemapOverride::AutoDetect => "AutoDetect",
+            RemapOverride::Enable => "Enable",
+            RemapOverride::Disable => "Disable",
+            RemapOverride::Unknown(_) => "Unknown",
+        }
+    }
+}
+
+impl From<RemapCfgOverride> for RemapOverride {
+    fn from(value: RemapCfgOverride) -> Self {
+        match value {
+            0 => RemapOverride::AutoDetect,
+            1 => RemapOverride::Enable,
+            2 => RemapOverride::Disable,
+            other => RemapOverride::Unknown(other),
+        }
+    }
+}
+
+impl From<RemapOverride> for RemapCfgOverride {
+    fn from(value: RemapOverride) -> Self {
+        match value {
+            RemapOverride::AutoDetect => 0,
+            RemapOverride::Enable => 1,
+            RemapOverride::Disable => 2,
+            RemapOverride::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for RemapOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemapOverride::Unknown(value) => write!(f, "Unknown({})", value),
+            _ => f.write_str(self.as_str()),
+        }
+    }
+}
+
+// binary structures
+//
+// These mirror the C++ structs and document the on-disk layout. Encoding and
+// decoding go field by field in little-endian order (see `Config::to_bytes`
+// and `Config::from_bytes`), so the output does not depend on the host.
+#[repr(C, packed(1))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct RemapCfgKey {
+    pub make_code: u16,
+    pub flags: u16,
+}
+
+impl RemapCfgKey {
+    pub const fn new() -> Self {
+        Self {
+            make_code: 0,
+            flags: 0,
+        }
+    }
+
+    pub const fn with_values(make_code: u16, flags: u16) -> Self {
+        Self { make_code, flags }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.make_code == 0 && self.flags == 0
+    }
+}
+
+#[repr(C, packed(1))]
+#[derive(Copy, Clone)]
+pub struct RemapCfg {
+    pub left_ctrl: RemapCfgKeyState,
+    pub left_alt: RemapCfgKeyState,
+    pub search: RemapCfgKeyState,
+    pub assistant: RemapCfgKeyState,
+    pub left_shift: RemapCfgKeyState,
+    pub right_ctrl: RemapCfgKeyState,
+    pub right_alt: RemapCfgKeyState,
+    pub right_shift: RemapCfgKeyState,
+    pub original_key: RemapCfgKey,
+    pub remap_vivaldi_to_fn_keys: u8,
+    pub remapped_key: RemapCfgKey,
+    pub additional_keys: [RemapCfgKey; MAX_ADDITIONAL_KEYS],
+}
+
+#[repr(C, packed(1))]
+pub struct RemapCfgsHeader {
+    pub magic: u32,
+    pub remappings: u32,
+    pub flip_search_and_assistant_on_pixelbook: u8,
+    pub has_assistant_key: RemapCfgOverride,
+    pub is_non_chrome_ec: RemapCfgOverride,
+}
+
+pub const HEADER_SIZE: usize = mem::size_of::<RemapCfgsHeader>();
+pub const CFG_SIZE: usize = mem::size_of::<RemapCfg>();
+
+// the driver reads these layouts directly; a size change breaks every file
+const _: () = assert!(HEADER_SIZE == 17, "RemapCfgsHeader must be 17 bytes");
+const _: () = assert!(CFG_SIZE == 73, "RemapCfg must be 73 bytes");
+
+// largest number of entries accepted when building a config
+pub const MAX_ENTRIES: usize = 255;
+
+// number of additional_keys slots in each entry
+pub const MAX_ADDITIONAL_KEYS: usize = 8;
+
+// owned model
+
+/// Header fields of a settings file. `remappings` is the count declared in
+/// the file; `Config::to_bytes` always writes the actual number of entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    pub remappings: u32,
+    pub flip_search_and_assistant_on_pixelbook: bool,
+    /// The flip byte as stored, when it is neither 0 nor 1.
+    pub flip_byte: Option<u8>,
+    pub has_assistant_key: RemapOverride,
+    pub is_non_chrome_ec: RemapOverride,
+}
+
+/// One of the eight modifier conditions of a `RemapCfg`, in field order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Modifier {
+    LeftCtrl,
+    LeftAlt,
+    Search,
+    Assistant,
+    LeftShift,
+    RightCtrl,
+    RightAlt,
+    RightShift,
+}
+
+impl Modifier {
+    pub const ALL: [Modifier; 8] = [
+        Modifier::LeftCtrl,
+        Modifier::LeftAlt,
+        Modifier::Search,
+        Modifier::Assistant,
+        Modifier::LeftShift,
+        Modifier::RightCtrl,
+        Modifier::RightAlt,
+        Modifier::RightShift,
+    ];
+
+    /// The `RemapCfg` field name.
+    pub fn field_name(self) -> &'static str {
+        match self {
+            Modifier::LeftCtrl => "left_ctrl",
+            Modifier::LeftAlt => "left_alt",
+            Modifier::Search => "search",
+            Modifier::Assistant => "assistant",
+            Modifier::LeftShift => "left_shift",
+            Modifier::RightCtrl => "right_ctrl",
+            Modifier::RightAlt => "right_alt",
+            Modifier::RightShift => "right_shift",
+        }
+    }
+
+    /// Short name used in reports and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::LeftCtrl => "LCtrl",
+            Modifier::LeftAlt => "LAlt",
+            Modifier::Search => "Search",
+            Modifier::Assistant => "Assistant",
+            Modifier::LeftShift => "LShift",
+            Modifier::RightCtrl => "RCtrl",
+            Modifier::RightAlt => "RAlt",
+            Modifier::RightShift => "RShift",
+        }
+    }
+
+    /// Looks up a modifier by its short name, case-insensitively. `Ctrl`,
+    /// `Alt` and `Shift` name the left-hand keys, and `LWin` is Search.
+    pub fn from_name(name: &str) -> Option<Modifier> {
+        let name = match name.to_ascii_lowercase().as_str() {
+            "ctrl" => "LCtrl",
+            "alt" => "LAlt",
+            "shift" => "LShift",
+            "lwin" => "Search",
+            _ => name,
+        };
+        Modifier::ALL.into_iter().find(|m| m.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The physical key that sets this modifier's bit.
+    pub fn key(self) -> RemapCfgKey {
+        match self {
+            Modifier::LeftCtrl => RemapCfgKey::with_values(K_LCTRL, 0),
+            Modifier::LeftAlt => RemapCfgKey::with_values(K_LALT, 0),
+            Modifier::Search => RemapCfgKey::with_values(K_LWIN, KEY_E0),
+            Modifier::Assistant => RemapCfgKey::with_values(K_ASSISTANT, KEY_E0),
+            Modifier::LeftShift => RemapCfgKey::with_values(K_LSHFT, 0),
+            Modifier::RightCtrl => RemapCfgKey::with_values(K_LCTRL, KEY_E0),
+            Modifier::RightAlt => RemapCfgKey::with_values(K_LALT, KEY_E0),
+            Modifier::RightShift => RemapCfgKey::with_values(K_RSHFT, 0),
+        }
+    }
+
+    /// The release of `key()`.
+    pub fn release(self) -> RemapCfgKey {
+        RemapCfgKey::with_values(self.key().make_code, self.key().flags | KEY_BREAK)
+    }
+
+    /// The modifier `key` sets, ignoring the break bit.
+    pub fn from_key(key: RemapCfgKey) -> Option<Modifier> {
+        let key = key_identity(key);
+        Modifier::ALL.into_iter().find(|m| m.key() == key)
+    }
+
+    /// Bit of this modifier in a `ModifierSet`.
+    pub const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A side-agnostic condition: either the left or the right key is held.
+/// Entries have no such field; rules using one expand into one entry per side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnyModifier {
+    Ctrl,
+    Alt,
+    Shift,
+}
+
+impl AnyModifier {
+    pub const ALL: [AnyModifier; 3] = [AnyModifier::Ctrl, AnyModifier::Alt, AnyModifier::Shift];
+
+    /// The left and the right modifier.
+    pub fn sides(self) -> [Modifier; 2] {
+        match self {
+            AnyModifier::Ctrl => [Modifier::LeftCtrl, Modifier::RightCtrl],
+            AnyModifier::Alt => [Modifier::LeftAlt, Modifier::RightAlt],
+            AnyModifier::Shift => [Modifier::LeftShift, Modifier::RightShift],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AnyModifier::Ctrl => "AnyCtrl",
+            AnyModifier::Alt => "AnyAlt",
+            AnyModifier::Shift => "AnyShift",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AnyModifier> {
+        AnyModifier::ALL.into_iter().find(|any| any.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Whether `key` is a release of the left or the right modifier.
+    pub fn is_side_release(self, key: RemapCfgKey) -> bool {
+        key.flags & KEY_BREAK != 0 && Modifier::from_key(key).is_some_and(|m| self.sides().contains(&m))
+    }
+}
+
+impl fmt::Display for AnyModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct ModifierSet(pub u8);
+
+impl ModifierSet {
+    pub const EMPTY: ModifierSet = ModifierSet(0);
+
+    /// Every one of the 256 combinations, in mask order.
+    pub fn all() -> impl Iterator<Item = ModifierSet> {
+        (0..=u8::MAX).map(ModifierSet)
+    }
+
+    pub fn contains(self, modifier: Modifier) -> bool {
+        self.0 & modifier.bit() != 0
+    }
+
+    pub fn insert(&mut self, modifier: Modifier) {
+        self.0 |= modifier.bit();
+    }
+
+    pub fn remove(&mut self, modifier: Modifier) {
+        self.0 &= !modifier.bit();
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Modifier> {
+        Modifier::ALL.into_iter().filter(move |&m| self.contains(m))
+    }
+}
+
+impl FromIterator<Modifier> for ModifierSet {
+    fn from_iter<I: IntoIterator<Item = Modifier>>(iter: I) -> Self {
+        let mut set = ModifierSet::EMPTY;
+        for modifier in iter {
+            set.insert(modifier);
+        }
+        set
+    }
+}
+
+/// Parses modifier names separated by `+`, `,` or spaces, e.g. `Ctrl+Shift`.
+/// `none` or an empty string is the empty set.
+impl FromStr for ModifierSet {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        text.split(['+', ',', ' '])
+            .map(str::trim)
+            .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("none"))
+            .map(|name| Modifier::from_name(name).ok_or_else(|| Error::UnknownModifier { name: name.to_string() }))
+            .collect()
+    }
+}
+
+impl fmt::Display for ModifierSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("none");
+        }
+        let names: Vec<&str> = self.iter().map(Modifier::name).collect();
+        f.write_str(&names.join("+"))
+    }
+}
+
+/// A single remap entry, the owned counterpart of `RemapCfg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rule {
+    pub left_ctrl: KeyState,
+    pub left_alt: KeyState,
+    pub search: KeyState,
+    pub assistant: KeyState,
+    pub left_shift: KeyState,
+    pub right_ctrl: KeyState,
+    pub right_alt: KeyState,
+    pub right_shift: KeyState,
+    pub original_key: RemapCfgKey,
+    pub remap_vivaldi_to_fn_keys: bool,
+    /// The remap_vivaldi_to_fn_keys byte as stored, when it is neither 0 nor 1.
+    pub fn_keys_byte: Option<u8>,
+    pub remapped_key: RemapCfgKey,
+    pub additional_keys: [RemapCfgKey; MAX_ADDITIONAL_KEYS],
+}
+
+impl Rule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Additional keys the driver injects: slot order, stopping at the first
+    /// empty slot. Keys after an empty slot are stored but never sent.
+    pub fn additional_keys(&self) -> impl Iterator<Item = &RemapCfgKey> {
+        self.additional_keys.iter().take_while(|key| !key.is_empty())
+    }
+
+    /// Releases of the modifiers this entry requires (`Enforce`), in field
+    /// order, leaving out those its additional keys already release. Injected
+    /// before the output, they send it without the modifiers that triggered
+    /// the entry.
+    pub fn trigger_releases(&self) -> Vec<RemapCfgKey> {
+        Modifier::ALL
+            .into_iter()
+            .filter(|&m| self.modifier(m) == KeyState::Enforce)
+            .map(Modifier::release)
+            .filter(|key| !self.additional_keys().any(|k| k == key))
+            .collect()
+    }
+
+    /// Whether the entry can match with Search held and its additional keys
+    /// release Search before pressing any other key. Windows takes that as a
+    /// tap of the Windows key and opens the Start menu.
+    pub fn releases_search_first(&self) -> bool {
+        let search_release = Modifier::Search.release();
+        self.search != KeyState::EnforceNot
+            && self.additional_keys().find(|key| key.flags & KEY_BREAK == 0 || **key == search_release)
+                == Some(&search_release)
+    }
+
+    pub fn modifier(&self, modifier: Modifier) -> KeyState {
+        match modifier {
+            Modifier::LeftCtrl => self.left_ctrl,
+            Modifier::LeftAlt => self.left_alt,
+            Modifier::Search => self.search,
+            Modifier::Assistant => self.assistant,
+            Modifier::LeftShift => self.left_shift,
+            Modifier::RightCtrl => self.right_ctrl,
+            Modifier::RightAlt => self.right_alt,
+            Modifier::RightShift => self.right_shift,
+        }
+    }
+
+    /// Whether the modifier conditions accept the given held modifiers.
+    pub fn matches_modifiers(&self, held: ModifierSet) -> bool {
+        Modifier::ALL.into_iter().all(|m| match self.modifier(m) {
+            KeyState::NoDetect | KeyState::Unknown(_) => true,
+            KeyState::Enforce => held.contains(m),
+            KeyState::EnforceNot => !held.contains(m),
+        })
+    }
+
+    /// Whether `key` is this entry's original key. Like the driver, only the
+    /// make code and the E0/E1 prefix are compared; the break bit is ignored.
+    pub fn matches_key(&self, key: RemapCfgKey) -> bool {
+        key_identity(self.original_key) == key_identity(key)
+    }
+
+    pub fn modifier_mut(&mut self, modifier: Modifier) -> &mut KeyState {
+        match modifier {
+            Modifier::LeftCtrl => &mut self.left_ctrl,
+            Modifier::LeftAlt => &mut self.left_alt,
+            Modifier::Search => &mut self.search,
+            Modifier::Assistant => &mut self.assistant,
+            Modifier::LeftShift => &mut self.left_shift,
+            Modifier::RightCtrl => &mut self.right_ctrl,
+            Modifier::RightAlt => &mut self.right_alt,
+            Modifier::RightShift => &mut self.right_shift,
+        }
+    }
+
+    /// `keys` with the releases of either side of `any` replaced by releases
+    /// of the sides this entry requires held, left first, written once where
+    /// the first of them was. An entry expanded from an `Enforce`d `any`
+    /// condition then releases the keys that are down, whichever side was
+    /// written.
+    pub(crate) fn release_held_sides(&self, any: AnyModifier, keys: &[RemapCfgKey]) -> Vec<RemapCfgKey> {
+        let held = any
+            .sides()
+            .into_iter()
+            .filter(|&side| self.modifier(side) == KeyState::Enforce)
+            .map(Modifier::release);
+        let mut rewritten = Vec::new();
+        let mut held = Some(held);
+        for &key in keys {
+            if !any.is_side_release(key) {
+                rewritten.push(key);
+            } else if let Some(held) = held.take() {
+                rewritten.extend(held);
+            }
+        }
+        rewritten
+    }
+
+    /// The entries that stand for this rule with `any` in `state`. EnforceNot
+    /// needs neither side held, a single entry. Enforce needs an entry per
+    /// side: left held, then right held without left. With `per_side`, for
+    /// an entry that releases its trigger modifiers, left only, right only
+    /// and both are separate, so each releases exactly the keys held; rules
+    /// that release one side explicitly need the same.
+    pub(crate) fn expand_any(
+        &self,
+        any: AnyModifier,
+        state: KeyState,
+        per_side: bool,
+    ) -> std::result::Result<Vec<Rule>, String> {
+        let [left, right] = any.sides();
+        if state == KeyState::NoDetect {
+            return Ok(vec![*self]);
+        }
+        if self.modifier(left) != KeyState::NoDetect || self.modifier(right) != KeyState::NoDetect {
+            return Err(format!("{} cannot be combined with {} or {} conditions", any, left, right));
+        }
+
+        let sides: &[(KeyState, KeyState)] = match state {
+            KeyState::EnforceNot => &[(KeyState::EnforceNot, KeyState::EnforceNot)],
+            KeyState::Enforce if per_side => &[
+                (KeyState::Enforce, KeyState::EnforceNot),
+                (KeyState::EnforceNot, KeyState::Enforce),
+                (KeyState::Enforce, KeyState::Enforce),
+            ],
+            KeyState::Enforce => &[(KeyState::Enforce, KeyState::NoDetect), (KeyState::EnforceNot, KeyState::Enforce)],
+            _ => return Err(format!("{} must be Enforce, EnforceNot or NoDetect", any)),
+        };
+        Ok(sides
+            .iter()
+            .map(|&(left_state, right_state)| {
+                let mut rule = *self;
+                *rule.modifier_mut(left) = left_state;
+                *rule.modifier_mut(right) = right_state;
+                rule
+            })
+            .collect())
+    }
+}
+
+/// A complete settings file: header plus remap entries in match order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub header: Header,
+    pub entries: Vec<Rule>,
+    /// Bytes after the declared entries, written back after them.
+    pub trailing: Vec<u8>,
+}
+
+impl Config {
+    pub fn new(header: Header, entries: Vec<Rule>) -> Self {
+        Self { header, entries, trailing: Vec::new() }
+    }
+
+    /// Index of the entry the driver applies to `key` with `held` modifiers:
+    /// the first one whose original key and conditions match.
+    pub fn first_match(&self, key: RemapCfgKey, held: ModifierSet) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|rule| rule.matches_key(key) && rule.matches_modifiers(held))
+    }
+
+    /// Distinct original keys in entry order.
+    pub fn original_keys(&self) -> Vec<RemapCfgKey> {
+        let mut keys: Vec<RemapCfgKey> = Vec::new();
+        for rule in &self.entries {
+            let key = key_identity(rule.original_key);
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    /// Size of the encoded file in bytes.
+    pub fn encoded_len(&self) -> usize {
+        HEADER_SIZE + CFG_SIZE * self.entries.len() + self.trailing.len()
+    }
+
+    /// Checks for values the driver does not define: unknown key states and
+    /// overrides. `from_bytes` accepts them so files round-trip unchanged.
+    pub fn validate(&self) -> Result<()> {
+        for (field, value, offset) in [
+            ("has_assistant_key", self.header.has_assistant_key, 0x0009),
+            ("is_non_chrome_ec", self.header.is_non_chrome_ec, 0x000D),
+        ] {
+            if let RemapOverride::Unknown(value) = value {
+                return Err(Error::UnknownOverride { field, value, offset });
+            }
+        }
+        for (entry, rule) in self.entries.iter().enumerate() {
+            for (i, modifier) in Modifier::ALL.into_iter().enumerate() {
+                if let KeyState::Unknown(value) = rule.modifier(modifier) {
+                    return Err(Error::UnknownKeyState {
+                        entry,
+                        field: modifier.field_name(),
+                        value,
+                        offset: HEADER_SIZE + entry * CFG_SIZE + i * 4,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes a settings file. Values this tool does not know and bytes after
+    /// the declared entries are kept, so `to_bytes` reproduces `data` exactly.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        // validate minimum file size
+        if data.len() < HEADER_SIZE {
+            return Err(Error::TruncatedHeader { len: data.len() });
+        }
+
+        // Read magic (0x0000-0x0003)
+        let magic = bytes_to_u32(&data[0..4]).unwrap();
+        if magic != CFG_MAGIC {
+            return Err(Error::BadMagic { found: magic });
+        }
+
+        let header = Header {
+            // Read remappings (0x0004-0x0007)
+            remappings: bytes_to_u32(&data[4..8]).unwrap(),
+            // Read flip_search_assistant (0x0008)
+            flip_search_and_assistant_on_pixelbook: data[8] != 0,
+            flip_byte: (data[8] > 1).then_some(data[8]),
+            // Read has_assistant_key (0x0009-0x000C)
+            has_assistant_key: bytes_to_i32(&data[9..13]).unwrap().into(),
+            // Read is_non_chrome_ec (0x000D-0x0010)
+            is_non_chrome_ec: bytes_to_i32(&data[13..17]).unwrap().into(),
+        };
+
+        let expected = HEADER_SIZE + header.remappings as usize * CFG_SIZE;
+        if data.len() < expected {
+            return Err(Error::SizeMismatch {
+                remappings: header.remappings,
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        let entries = (0..header.remappings as usize).map(|i| decode_rule(data, i)).collect();
+        let trailing = data[expected..].to_vec();
+
+        Ok(Self { header, entries, trailing })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Encodes the config in the driver's little-endian layout. The header
+    /// count is taken from `entries`, not from `header.remappings`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.encoded_len());
+
+        // Write header
+        buffer.extend_from_slice(&CFG_MAGIC.to_le_bytes());
+        buffer.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        buffer.push(encode_bool(self.header.flip_search_and_assistant_on_pixelbook, self.header.flip_byte));
+        buffer.extend_from_slice(&RemapCfgOverride::from(self.header.has_assistant_key).to_le_bytes());
+        buffer.extend_from_slice(&RemapCfgOverride::from(self.header.is_non_chrome_ec).to_le_bytes());
+
+        for rule in &self.entries {
+            encode_rule(rule, &mut buffer);
+        }
+        buffer.extend_from_slice(&self.trailing);
+
+        debug_assert_eq!(buffer.len(), self.encoded_len());
+        buffer
+    }
+
+    pub fn from_json(json_data: &str) -> Result<Self> {
+        let config_json: ConfigFileJson = serde_json::from_str(json_data)?;
+        config_json.to_config()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&ConfigFileJson::from(self))?)
+    }
+}
+
+/// A key with the break bit cleared, as the driver compares original keys.
+pub fn key_identity(key: RemapCfgKey) -> RemapCfgKey {
+    RemapCfgKey::with_values(key.make_code, key.flags & !KEY_BREAK)
+}
+
+/// Moves a release of Search that comes before every pressed key in `keys`
+/// to just after the first one, so a trigger release does not make Windows
+/// see a Search tap. Keys with nothing pressed are left as they are.
+pub(crate) fn defer_search_release(keys: &mut Vec<RemapCfgKey>) {
+    let search_release = Modifier::Search.release();
+    let Some(release) = keys.iter().position(|&key| key == search_release) else { return };
+    if let Some(pressed) = keys.iter().position(|key| key.flags & KEY_BREAK == 0)
+        && release < pressed
+    {
+        keys.remove(release);
+        keys.insert(pressed, search_release);
+    }
+}
+
+fn decode_rule(data: &[u8], entry: usize) -> Rule {
+    let offset = HEADER_SIZE + entry * CFG_SIZE;
+    let config_data = &data[offset..offset + CFG_SIZE];
+    let key_state = |field_offset: usize| KeyState::from(bytes_to_i32(&config_data[field_offset..field_offset + 4]).unwrap());
+
+    let mut rule = Rule::new();
+
+    // Read modifier states
+    rule.left_ctrl = key_state(0x00);
+    rule.left_alt = key_state(0x04);
+    rule.search = key_state(0x08);
+    rule.assistant = key_state(0x0C);
+    rule.left_shift = key_state(0x10);
+    rule.right_ctrl = key_state(0x14);
+    rule.right_alt = key_state(0x18);
+    rule.right_shift = key_state(0x1C);
+
+    // Read original key
+    rule.original_key = decode_key(&config_data[0x20..0x24]);
+
+    // Read remap flag
+    rule.remap_vivaldi_to_fn_keys = config_data[0x24] != 0;
+    rule.fn_keys_byte = (config_data[0x24] > 1).then_some(config_data[0x24]);
+
+    // Read remapped key
+    rule.remapped_key = decode_key(&config_data[0x25..0x29]);
+
+    // Read additional keys
+    for (j, key) in rule.additional_keys.iter_mut().enumerate() {
+        let key_offset = 0x29 + (j * 4);
+        *key = decode_key(&config_data[key_offset..key_offset + 4]);
+    }
+
+    rule
+}
+
+// a stored byte other than 0/1 is written back as long as it still agrees
+// with the flag
+fn encode_bool(flag: bool, byte: Option<u8>) -> u8 {
+    match byte {
+        Some(byte) if (byte != 0) == flag => byte,
+        _ => flag as u8,
+    }
+}
+
+fn encode_rule(rule: &Rule, buffer: &mut Vec<u8>) {
+    // Write modifier states
+    for state in [
+        rule.left_ctrl,
+        rule.left_alt,
+        rule.search,
+        rule.assistant,
+        rule.left_shift,
+        rule.right_ctrl,
+        rule.right_alt,
+        rule.right_shift,
+    ] {
+        buffer.extend_from_slice(&RemapCfgKeyState::from(state).to_le_bytes());
+    }
+
+    encode_key(rule.original_key, buffer);
+    buffer.push(encode_bool(rule.remap_vivaldi_to_fn_keys, rule.fn_keys_byte));
+    encode_key(rule.remapped_key, buffer);
+
+    for key in rule.additional_keys {
+        encode_key(key, buffer);
+    }
+}
+
+fn encode_key(key: RemapCfgKey, buffer: &mut Vec<u8>) {
+    buffer.extend_from_slice(&{ key.make_code }.to_le_bytes());
+    buffer.extend_from_slice(&{ key.flags }.to_le_bytes());
+}
+
+fn decode_key(bytes: &[u8]) -> RemapCfgKey {
+    RemapCfgKey::with_values(
+        bytes_to_u16(&bytes[0..2]).unwrap(),
+        bytes_to_u16(&bytes[2..4]).unwrap(),
+    )
+}
+
+//helper
+fn bytes_to_u32(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn bytes_to_i32(bytes: &[u8]) -> Option<i32> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn bytes_to_u16(bytes: &[u8]) -> Option<u16> {
+    if bytes.len() < 2 {
+        return None;
+    }
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo::demo_config;
+
+    #[test]
+    fn short_file_is_a_truncated_header() {
+        let data = demo_config();
+        assert!(matches!(Config::from_bytes(&data[..10]), Err(Error::TruncatedHeader { len: 10 })));
+    }
+
+    #[test]
+    fn wrong_magic_is_reported_at_offset_zero() {
+        let mut data = demo_config();
+        data[0..4].copy_from_slice(b"ABCD");
+        let error = Config::from_bytes(&data).unwrap_err();
+        assert!(matches!(error, Error::BadMagic { found } if found == u32::from_le_bytes(*b"ABCD")));
+        assert!(error.to_string().contains("offset 0x0000"));
+    }
+
+    #[test]
+    fn missing_entries_are_a_size_mismatch() {
+        let data = demo_config();
+        let error = Config::from_bytes(&data[..data.len() - 1]).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::SizeMismatch { remappings: 40, expected: 2937, actual: 2936 }
+        ));
+    }
+
+    #[test]
+    fn unknown_key_state_is_reported_at_its_offset() {
+        let mut data = demo_config();
+        // right_alt (the seventh state) of entry 2
+        let offset = HEADER_SIZE + 2 * CFG_SIZE + 6 * 4;
+        data[offset..offset + 4].copy_from_slice(&5i32.to_le_bytes());
+        let config = Config::from_bytes(&data).unwrap();
+        let error = config.validate().unwrap_err();
+        assert!(matches!(
+            error,
+            Error::UnknownKeyState { entry: 2, field: "right_alt", value: 5, offset: o } if o == offset
+        ));
+        assert!(error.to_string().ends_with(&format!("offset 0x{:04X}", offset)));
+    }
+
+    #[test]
+    fn unknown_override_is_reported_at_its_offset() {
+        let mut data = demo_config();
+        data[0x0D..0x11].copy_from_slice(&(-1i32).to_le_bytes());
+        let config = Config::from_bytes(&data).unwrap();
+        assert!(matches!(
+            config.validate(),
+            Err(Error::UnknownOverride { field: "is_non_chrome_ec", value: -1, offset: 0x000D })
+        ));
+    }
+
+    #[test]
+    fn unknown_values_round_trip() {
+        let mut data = demo_config();
+        data[8] = 7;
+        data[0x09..0x0D].copy_from_slice(&3i32.to_le_bytes());
+        data.extend_from_slice(&[1, 2, 3]);
+        let config = Config::from_bytes(&data).unwrap();
+        assert_eq!(config.header.flip_byte, Some(7));
+        assert_eq!(config.header.has_assistant_key, RemapOverride::Unknown(3));
+        assert_eq!(config.trailing, [1, 2, 3]);
+        assert_eq!(config.to_bytes(), data);
+    }
+
+    #[test]
+    fn modifier_keys_round_trip() {
+        for modifier in Modifier::ALL {
+            assert_eq!(Modifier::from_key(modifier.key()), Some(modifier));
+            assert_eq!(Modifier::from_key(modifier.release()), Some(modifier));
+        }
+        assert_eq!(Modifier::from_key(RemapCfgKey::with_values(0x1E, 0)), None);
+    }
+
+    #[test]
+    fn trigger_releases_skip_keys_already_released() {
+        let mut rule = Rule::new();
+        rule.left_ctrl = KeyState::Enforce;
+        rule.search = KeyState::Enforce;
+        rule.left_alt = KeyState::EnforceNot;
+        let lctrl_break = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
+        let lwin_break = RemapCfgKey::with_values(K_LWIN, KEY_E0 | KEY_BREAK);
+        assert_eq!(rule.trigger_releases(), [lctrl_break, lwin_break]);
+
+        rule.additional_keys[0] = lwin_break;
+        assert_eq!(rule.trigger_releases(), [lctrl_break]);
+    }
+
+    #[test]
+    fn search_release_moves_after_the_first_pressed_key() {
+        let lctrl = RemapCfgKey::with_values(K_LCTRL, 0);
+        let search_release = Modifier::Search.release();
+        let mut keys = vec![search_release, Modifier::LeftAlt.release(), lctrl, lctrl];
+        defer_search_release(&mut keys);
+        assert_eq!(keys, [Modifier::LeftAlt.release(), lctrl, search_release, lctrl]);
+
+        let mut keys = vec![lctrl, search_release];
+        defer_search_release(&mut keys);
+        assert_eq!(keys, [lctrl, search_release]);
+
+        let mut keys = vec![search_release];
+        defer_search_release(&mut keys);
+        assert_eq!(keys, [search_release]);
+    }
+}
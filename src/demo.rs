@@ -0,0 +1,13 @@
+use crate::config::Config;
+use crate::presets::PRESETS;
+
+/// The default 40-entry layout, matching coolstar's VivaldiKeyboardTester.
+/// Defined as the `default` preset in `src/presets/default.rules`.
+pub fn default_config() -> Config {
+    PRESETS[0].config()
+}
+
+/// `default_config` encoded, byte-identical to the reference `croskbsettings.bin`.
+pub fn demo_config() -> Vec<u8> {
+    default_config().to_bytes()
+}
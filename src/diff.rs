@@ -0,0 +1,267 @@
+//! Field-by-field comparison of two decoded settings files.
+//!
+//! Entries are paired first by trigger (original key and all eight modifier
+//! conditions), then remaining entries by original key alone, in file order.
+//! Paired entries report the fields that differ; unpaired ones are added or
+//! removed.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::config::{Config, Modifier, RemapCfgKey, Rule};
+use crate::dsl::format_rule;
+use crate::error::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum EntryDiff {
+    Removed { old_index: usize, rule: String },
+    Added { new_index: usize, rule: String },
+    /// An unchanged entry whose position relative to the others changed.
+    Moved { old_index: usize, new_index: usize, rule: String },
+    Changed { old_index: usize, new_index: usize, rule: String, fields: Vec<FieldChange> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConfigDiff {
+    pub header: Vec<FieldChange>,
+    pub entries: Vec<EntryDiff>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.entries.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+pub fn diff(old: &Config, new: &Config) -> ConfigDiff {
+    let mut result = ConfigDiff::default();
+
+    let (a, b) = (&old.header, &new.header);
+    push_change(&mut result.header, "remappings", a.remappings, b.remappings);
+    push_change(
+        &mut result.header,
+        "flip_search_and_assistant_on_pixelbook",
+        a.flip_search_and_assistant_on_pixelbook,
+        b.flip_search_and_assistant_on_pixelbook,
+    );
+    push_change(
+        &mut result.header,
+        "flip_search_and_assistant_on_pixelbook_byte",
+        byte_text(a.flip_byte),
+        byte_text(b.flip_byte),
+    );
+    push_change(&mut result.header, "has_assistant_key", a.has_assistant_key, b.has_assistant_key);
+    push_change(&mut result.header, "is_non_chrome_ec", a.is_non_chrome_ec, b.is_non_chrome_ec);
+    push_change(&mut result.header, "trailing_bytes_hex", hex_text(&old.trailing), hex_text(&new.trailing));
+
+    // pairing[i] is the new index for old entry i
+    let mut pairing: Vec<Option<usize>> = vec![None; old.entries.len()];
+    let mut taken = vec![false; new.entries.len()];
+    let passes: [fn(&Rule, &Rule) -> bool; 2] = [same_trigger, |a, b| a.matches_key(b.original_key)];
+    for same in passes {
+        for (i, rule) in old.entries.iter().enumerate() {
+            if pairing[i].is_some() {
+                continue;
+            }
+            if let Some(j) = (0..new.entries.len()).find(|&j| !taken[j] && same(rule, &new.entries[j])) {
+                pairing[i] = Some(j);
+                taken[j] = true;
+            }
+        }
+    }
+
+    let in_order = longest_ordered(&pairing);
+    for (i, rule) in old.entries.iter().enumerate() {
+        let Some(j) = pairing[i] else {
+            result.entries.push(EntryDiff::Removed { old_index: i, rule: format_rule(rule) });
+            continue;
+        };
+        let fields = rule_changes(rule, &new.entries[j]);
+        if !fields.is_empty() {
+            result.entries.push(EntryDiff::Changed { old_index: i, new_index: j, rule: format_rule(rule), fields });
+        } else if !in_order[i] {
+            result.entries.push(EntryDiff::Moved { old_index: i, new_index: j, rule: format_rule(rule) });
+        }
+    }
+    for (j, rule) in new.entries.iter().enumerate() {
+        if !taken[j] {
+            result.entries.push(EntryDiff::Added { new_index: j, rule: format_rule(rule) });
+        }
+    }
+    result
+}
+
+// marks the largest set of paired entries that kept their relative order;
+// only the others are reported as moved, not everything after an insertion
+fn longest_ordered(pairing: &[Option<usize>]) -> Vec<bool> {
+    let n = pairing.len();
+    let mut length = vec![0usize; n];
+    let mut previous = vec![None; n];
+    for i in 0..n {
+        let Some(j) = pairing[i] else { continue };
+        length[i] = 1;
+        for k in 0..i {
+            if pairing[k].is_some_and(|l| l < j) && length[k] + 1 > length[i] {
+                length[i] = length[k] + 1;
+                previous[i] = Some(k);
+            }
+        }
+    }
+
+    let mut in_order = vec![false; n];
+    let mut current = (0..n).filter(|&i| length[i] > 0).max_by_key(|&i| length[i]);
+    while let Some(i) = current {
+        in_order[i] = true;
+        current = previous[i];
+    }
+    in_order
+}
+
+fn same_trigger(a: &Rule, b: &Rule) -> bool {
+    a.matches_key(b.original_key) && Modifier::ALL.into_iter().all(|m| a.modifier(m) == b.modifier(m))
+}
+
+fn push_change<T: PartialEq + fmt::Display>(changes: &mut Vec<FieldChange>, field: &str, old: T, new: T) {
+    if old != new {
+        changes.push(FieldChange { field: field.to_string(), old: old.to_string(), new: new.to_string() });
+    }
+}
+
+fn rule_changes(old: &Rule, new: &Rule) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    for modifier in Modifier::ALL {
+        push_change(&mut changes, modifier.field_name(), old.modifier(modifier), new.modifier(modifier));
+    }
+    push_change(&mut changes, "original_key", key_text(old.original_key), key_text(new.original_key));
+    push_change(
+        &mut changes,
+        "remap_vivaldi_to_fn_keys",
+        old.remap_vivaldi_to_fn_keys,
+        new.remap_vivaldi_to_fn_keys,
+    );
+    push_change(&mut changes, "remap_vivaldi_to_fn_byte", byte_text(old.fn_keys_byte), byte_text(new.fn_keys_byte));
+    push_change(&mut changes, "remapped_key", key_text(old.remapped_key), key_text(new.remapped_key));
+    for (slot, (a, b)) in old.additional_keys.iter().zip(&new.additional_keys).enumerate() {
+        push_change(&mut changes, &format!("additional_keys[{}]", slot), key_text(*a), key_text(*b));
+    }
+    changes
+}
+
+fn key_text(key: RemapCfgKey) -> String {
+    if key.is_empty() { "(empty)".to_string() } else { key.to_string() }
+}
+
+// a flag byte kept because it is neither 0 nor 1
+fn byte_text(byte: Option<u8>) -> String {
+    byte.map_or_else(|| "(none)".to_string(), |byte| format!("0x{:02X}", byte))
+}
+
+fn hex_text(bytes: &[u8]) -> String {
+    if bytes.is_empty() { "(none)".to_string() } else { bytes.iter().map(|b| format!("{:02X}", b)).collect() }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.header {
+            writeln!(f, "header {}: {} -> {}", change.field, change.old, change.new)?;
+        }
+        for entry in &self.entries {
+            match entry {
+                EntryDiff::Removed { old_index, rule } => writeln!(f, "- config {}: {}", old_index, rule)?,
+                EntryDiff::Added { new_index, rule } => writeln!(f, "+ config {}: {}", new_index, rule)?,
+                EntryDiff::Moved { old_index, new_index, rule } => {
+                    writeln!(f, "> config {} -> {}: {}", old_index, new_index, rule)?
+                }
+                EntryDiff::Changed { old_index, new_index, rule, fields } => {
+                    writeln!(f, "~ config {} -> {}: {}", old_index, new_index, rule)?;
+                    for change in fields {
+                        writeln!(f, "    {}: {} -> {}", change.field, change.old, change.new)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo::demo_config;
+
+    fn demo() -> Config {
+        Config::from_bytes(&demo_config()).unwrap()
+    }
+
+    #[test]
+    fn identical_configs_have_no_differences() {
+        assert!(diff(&demo(), &demo()).is_empty());
+    }
+
+    #[test]
+    fn trailing_bytes_are_a_header_change() {
+        let mut data = demo_config();
+        data.extend_from_slice(&[1, 2, 3]);
+        let result = diff(&demo(), &Config::from_bytes(&data).unwrap());
+        assert_eq!(
+            result.header,
+            [FieldChange { field: "trailing_bytes_hex".to_string(), old: "(none)".to_string(), new: "010203".to_string() }]
+        );
+        assert!(result.entries.is_empty());
+    }
+
+    #[test]
+    fn stored_flip_byte_is_a_header_change() {
+        let mut data = demo_config();
+        data[8] = 7;
+        let new = Config::from_bytes(&data).unwrap();
+        assert_eq!(
+            diff(&demo(), &new).header,
+            [FieldChange {
+                field: "flip_search_and_assistant_on_pixelbook_byte".to_string(),
+                old: "(none)".to_string(),
+                new: "0x07".to_string(),
+            }]
+        );
+        assert!(diff(&demo(), &new).to_string().contains("flip_search_and_assistant_on_pixelbook_byte: (none) -> 0x07"));
+    }
+
+    #[test]
+    fn stored_fn_byte_is_an_entry_change() {
+        let old = demo();
+        let mut new = old.clone();
+        let i = new.entries.iter().position(|rule| rule.remap_vivaldi_to_fn_keys).unwrap();
+        new.entries[i].fn_keys_byte = Some(2);
+        let new = Config::from_bytes(&new.to_bytes()).unwrap();
+
+        let result = diff(&old, &new);
+        assert!(result.header.is_empty());
+        match &result.entries[..] {
+            [EntryDiff::Changed { old_index, new_index, fields, .. }] => {
+                assert_eq!((*old_index, *new_index), (i, i));
+                assert_eq!(
+                    fields,
+                    &[FieldChange {
+                        field: "remap_vivaldi_to_fn_byte".to_string(),
+                        old: "(none)".to_string(),
+                        new: "0x02".to_string(),
+                    }]
+                );
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,576 @@
+//! Line-oriented text format for remap rules.
+//!
+//! Each non-empty line is either a header setting or one rule:
+//!
+//! ```text
+//! # Ctrl + Overview -> Windows + Tab
+//! set flip_search_and_assistant_on_pixelbook = true
+//! Ctrl !Shift !Search + Overview => release LCtrl + LWin + Tab
+//! !Ctrl + Vivaldi.Back => Fn
+//! ```
+//!
+//! The left side lists modifier conditions (`Ctrl`, `!Shift`, ...) followed by
+//! the original key. `AnyCtrl`, `AnyAlt` and `AnyShift` accept either side and
+//! become one entry per side; `!AnyCtrl` requires neither side held.
+//!
+//! The right side is a `+`-separated list of output keys: the last pressed key
+//! becomes `remapped_key`, every other key is injected as an additional key in
+//! the order written, and `release K` injects a break of `K`. `release
+//! modifiers` releases every modifier the trigger requires that is not
+//! released explicitly, so `Ctrl Shift + X => release modifiers + Y` sends a
+//! bare `Y`, and with `AnyCtrl` releases whichever Ctrl keys are held. Its
+//! release of Search comes after the first pressed key, if there is one. With
+//! `AnyCtrl`, `release LCtrl` or `release RCtrl` does the same for Ctrl.
+//! `ensure K` holds modifier `K` for the output: the rule becomes one entry
+//! that requires the modifier not held and injects `K`, and one that requires
+//! it held and leaves `K` out, so `Ctrl + Overview => release LCtrl + ensure
+//! LWin + Tab` sends Windows + Tab whether or not Search is held. `Fn` sets
+//! `remap_vivaldi_to_fn_keys` and `None` stands for an empty remapped key.
+//! `#` starts a comment. A condition the driver does not define is written
+//! with its raw value, e.g. `Ctrl=3`.
+
+use crate::config::{
+    defer_search_release, AnyModifier, Config, Header, KeyState, Modifier, RemapCfgKey, RemapOverride, Rule,
+    MAX_ADDITIONAL_KEYS, MAX_ENTRIES,
+};
+use crate::error::{Error, Result};
+use crate::keys::{format_key, parse_key, KEY_BREAK};
+
+// one item of a rule's output side
+enum Output {
+    Press(RemapCfgKey),
+    Release(RemapCfgKey),
+    // `release modifiers`
+    ReleaseTrigger,
+    // `ensure K`: K is pressed unless the entry requires it held
+    Ensure(Modifier),
+    Empty,
+}
+
+/// Compiles rule text into a config. Entries keep the order of the lines.
+pub fn compile(text: &str) -> Result<Config> {
+    let mut header = Header::default();
+    let mut entries = Vec::new();
+
+    for (i, raw_line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let syntax = |message: String| Error::Syntax { line: line_no, message };
+
+        if let Some(setting) = line.strip_prefix("set ") {
+            parse_setting(&mut header, setting).map_err(syntax)?;
+        } else {
+            entries.extend(parse_rule(line).map_err(syntax)?);
+        }
+    }
+
+    if entries.len() > MAX_ENTRIES {
+        return Err(Error::TooManyEntries { count: entries.len(), max: MAX_ENTRIES });
+    }
+
+    header.remappings = entries.len() as u32;
+    Ok(Config::new(header, entries))
+}
+
+fn parse_setting(header: &mut Header, setting: &str) -> std::result::Result<(), String> {
+    let (name, value) = setting
+        .split_once('=')
+        .ok_or_else(|| format!("expected 'set <name> = <value>', got 'set {}'", setting))?;
+    let (name, value) = (name.trim(), value.trim());
+
+    let parse_override = |value: &str| match value {
+        "AutoDetect" => Ok(RemapOverride::AutoDetect),
+        "Enable" => Ok(RemapOverride::Enable),
+        "Disable" => Ok(RemapOverride::Disable),
+        other => other
+            .parse::<i32>()
+            .map(RemapOverride::from)
+            .map_err(|_| format!("expected AutoDetect, Enable or Disable, got '{}'", other)),
+    };
+
+    match name {
+        "flip_search_and_assistant_on_pixelbook" => {
+            header.flip_search_and_assistant_on_pixelbook = value
+                .parse()
+                .map_err(|_| format!("expected true or false, got '{}'", value))?;
+        }
+        "has_assistant_key" => header.has_assistant_key = parse_override(value)?,
+        "is_non_chrome_ec" => header.is_non_chrome_ec = parse_override(value)?,
+        other => return Err(format!("unknown setting '{}'", other)),
+    }
+    Ok(())
+}
+
+// one line, which `ensure` and `Any` conditions may split into several entries
+fn parse_rule(line: &str) -> std::result::Result<Vec<Rule>, String> {
+    let (trigger, output) = line
+        .split_once("=>")
+        .ok_or_else(|| "expected '<modifiers> + <key> => <output>'".to_string())?;
+
+    let mut rule = Rule::new();
+    let mut anys: Vec<(AnyModifier, KeyState)> = Vec::new();
+
+    // trigger: modifier words, then the original key as the last '+' part
+    let mut parts: Vec<&str> = trigger.split('+').map(str::trim).collect();
+    let key = parts.pop().unwrap_or_default();
+    for word in parts.iter().flat_map(|part| part.split_whitespace()) {
+        let (state, name) = match (word.strip_prefix('!'), word.split_once('=')) {
+            (Some(name), _) => (KeyState::EnforceNot, name),
+            (None, Some((name, value))) => {
+                let value: i32 = value.parse().map_err(|_| format!("invalid key state '{}'", word))?;
+                (KeyState::from(value), name)
+            }
+            (None, None) => (KeyState::Enforce, word),
+        };
+        if let Some(any) = AnyModifier::from_name(name) {
+            anys.push((any, state));
+            continue;
+        }
+        let modifier = Modifier::from_name(name).ok_or_else(|| format!("unknown modifier '{}'", name))?;
+        *rule.modifier_mut(modifier) = state;
+    }
+    rule.original_key = parse_key(key).map_err(|e| e.to_string())?;
+
+    // output: the last pressed key is the remapped key, the rest are
+    // injected as additional keys in the order written
+    let mut outputs = Vec::new();
+    for item in output.split('+') {
+        let mut segments = split_release(item).into_iter();
+        let pressed = segments.next().unwrap_or_default();
+
+        if pressed.eq_ignore_ascii_case("Fn") {
+            rule.remap_vivaldi_to_fn_keys = true;
+        } else if pressed.eq_ignore_ascii_case("None") {
+            outputs.push(Output::Empty);
+        } else if let Some(name) = strip_keyword(&pressed, "ensure") {
+            let key = parse_key(name).map_err(|e| e.to_string())?;
+            let modifier = Modifier::from_key(key).ok_or_else(|| format!("ensure needs a modifier key, got '{}'", name))?;
+            outputs.push(Output::Ensure(modifier));
+        } else if !pressed.is_empty() {
+            outputs.push(Output::Press(parse_key(&pressed).map_err(|e| e.to_string())?));
+        }
+
+        for released in segments {
+            if released.eq_ignore_ascii_case("modifiers") {
+                outputs.push(Output::ReleaseTrigger);
+            } else {
+                outputs.push(Output::Release(parse_key(&released).map_err(|e| e.to_string())?));
+            }
+        }
+    }
+
+    if let Some(last) = outputs.iter().rposition(|o| matches!(o, Output::Press(_) | Output::Empty))
+        && let Output::Press(key) = outputs.remove(last)
+    {
+        rule.remapped_key = key;
+    }
+
+    let consumes = outputs.iter().any(|output| matches!(output, Output::ReleaseTrigger));
+    let ensured: Vec<Modifier> = outputs
+        .iter()
+        .filter_map(|output| match *output {
+            Output::Ensure(modifier) => Some(modifier),
+            _ => None,
+        })
+        .collect();
+
+    // side-agnostic conditions become one entry per side; with `release
+    // modifiers` or a release of either side, one per combination of sides,
+    // so each releases those held
+    let mut variants = vec![rule];
+    let mut released_anys = Vec::new();
+    for &(any, state) in &anys {
+        let ensures_side = any.sides().iter().any(|side| ensured.contains(side));
+        let releases_side = outputs
+            .iter()
+            .any(|output| matches!(*output, Output::Release(key) if any.is_side_release(released(key))));
+        if releases_side && state == KeyState::Enforce {
+            if ensures_side {
+                return Err(format!("{} cannot be combined with releasing one side and ensuring the other", any));
+            }
+            released_anys.push(any);
+        }
+        let per_side = (consumes || releases_side) && !ensures_side;
+        let mut expanded = Vec::new();
+        for variant in &variants {
+            expanded.extend(variant.expand_any(any, state, per_side)?);
+        }
+        variants = expanded;
+    }
+
+    let mut rules = Vec::new();
+    for variant in variants {
+        for split in split_ensured(variant, &ensured)? {
+            rules.push(fill_additional_keys(split, &outputs, &released_anys)?);
+        }
+    }
+    Ok(rules)
+}
+
+// one entry per combination of ensured modifiers the rule leaves open, the
+// first one written varying fastest
+fn split_ensured(rule: Rule, ensured: &[Modifier]) -> std::result::Result<Vec<Rule>, String> {
+    let mut open: Vec<Modifier> = Vec::new();
+    for &modifier in ensured {
+        match rule.modifier(modifier) {
+            KeyState::NoDetect if !open.contains(&modifier) => open.push(modifier),
+            KeyState::Unknown(_) => {
+                return Err(format!("ensure cannot be combined with an unknown {} state", modifier));
+            }
+            _ => {}
+        }
+    }
+    Ok((0..1usize << open.len())
+        .map(|mask| {
+            let mut variant = rule;
+            for (i, &modifier) in open.iter().enumerate() {
+                let held = mask >> i & 1 == 1;
+                *variant.modifier_mut(modifier) = if held { KeyState::Enforce } else { KeyState::EnforceNot };
+            }
+            variant
+        })
+        .collect())
+}
+
+// a release of either side of `released_anys` becomes releases of the sides
+// the entry requires held
+fn fill_additional_keys(
+    mut rule: Rule,
+    outputs: &[Output],
+    released_anys: &[AnyModifier],
+) -> std::result::Result<Rule, String> {
+    // `release modifiers` leaves out explicit releases and ensured modifiers
+    let kept: Vec<RemapCfgKey> = outputs
+        .iter()
+        .filter_map(|output| match *output {
+            Output::Release(key) => Some(released(key)),
+            Output::Ensure(modifier) => Some(released(modifier.key())),
+            _ => None,
+        })
+        .collect();
+    let consumes = outputs.iter().any(|output| matches!(output, Output::ReleaseTrigger));
+    let keys: Vec<RemapCfgKey> = outputs
+        .iter()
+        .flat_map(|output| match *output {
+            Output::Press(key) => vec![key],
+            Output::Release(key) => vec![released(key)],
+            Output::ReleaseTrigger => {
+                rule.trigger_releases().into_iter().filter(|key| !kept.contains(key)).collect()
+            }
+            Output::Ensure(modifier) if rule.modifier(modifier) == KeyState::Enforce => Vec::new(),
+            Output::Ensure(modifier) => vec![modifier.key()],
+            Output::Empty => Vec::new(),
+        })
+        .collect();
+    let mut keys = released_anys.iter().fold(keys, |keys, &any| rule.release_held_sides(any, &keys));
+    // a Search release from `release modifiers` waits for the first pressed key
+    if consumes && !kept.contains(&Modifier::Search.release()) {
+        defer_search_release(&mut keys);
+    }
+    if keys.len() > MAX_ADDITIONAL_KEYS {
+        return Err(format!(
+            "{} additional keys{}, at most {} fit in one entry",
+            keys.len(),
+            if consumes { " after releasing the modifiers" } else { "" },
+            MAX_ADDITIONAL_KEYS
+        ));
+    }
+    for (slot, key) in rule.additional_keys.iter_mut().zip(keys) {
+        *slot = key;
+    }
+
+    Ok(rule)
+}
+
+fn released(key: RemapCfgKey) -> RemapCfgKey {
+    RemapCfgKey::with_values(key.make_code, key.flags | KEY_BREAK)
+}
+
+// "ensure LWin" -> "LWin"
+fn strip_keyword<'a>(item: &'a str, keyword: &str) -> Option<&'a str> {
+    let (first, rest) = item.split_once(char::is_whitespace)?;
+    first.eq_ignore_ascii_case(keyword).then(|| rest.trim())
+}
+
+// splits "Tab release LCtrl release LShift" into ["Tab", "LCtrl", "LShift"]
+fn split_release(item: &str) -> Vec<String> {
+    let mut segments = vec![Vec::new()];
+    for word in item.split_whitespace() {
+        if word.eq_ignore_ascii_case("release") {
+            segments.push(Vec::new());
+        } else {
+            segments.last_mut().unwrap().push(word);
+        }
+    }
+    segments.into_iter().map(|words| words.join(" ")).collect()
+}
+
+/// Writes a config as rule text that compiles back to entries that behave the
+/// same. Raw flag bytes, unknown key flag bits, trailing bytes and additional
+/// keys after an empty slot, which the driver never sends, have no text form;
+/// use JSON to keep those.
+pub fn decompile(config: &Config) -> String {
+    let header = &config.header;
+    let mut text = String::new();
+
+    text.push_str(&format!(
+        "set flip_search_and_assistant_on_pixelbook = {}\n",
+        header.flip_search_and_assistant_on_pixelbook
+    ));
+    text.push_str(&format!("set has_assistant_key = {}\n", format_override(header.has_assistant_key)));
+    text.push_str(&format!("set is_non_chrome_ec = {}\n\n", format_override(header.is_non_chrome_ec)));
+
+    for (i, rule) in config.entries.iter().enumerate() {
+        text.push_str(&format!("{:<56} # {}\n", format_rule(rule), i));
+    }
+    text
+}
+
+// unknown overrides are written as their raw value, which parse_setting accepts
+fn format_override(value: RemapOverride) -> String {
+    match value {
+        RemapOverride::Unknown(value) => value.to_string(),
+        known => known.to_string(),
+    }
+}
+
+pub fn format_rule(rule: &Rule) -> String {
+    let mut trigger = Vec::new();
+    for modifier in Modifier::ALL {
+        let name = modifier.name();
+        match rule.modifier(modifier) {
+            KeyState::Enforce => trigger.push(name.to_string()),
+            KeyState::EnforceNot => trigger.push(format!("!{}", name)),
+            KeyState::Unknown(value) => trigger.push(format!("{}={}", name, value)),
+            KeyState::NoDetect => {}
+        }
+    }
+
+    let mut output = Vec::new();
+    for key in rule.additional_keys() {
+        if key.flags & KEY_BREAK != 0 {
+            let pressed = RemapCfgKey::with_values(key.make_code, key.flags & !KEY_BREAK);
+            output.push(format!("release {}", format_dsl_key(pressed)));
+        } else {
+            output.push(format_dsl_key(*key));
+        }
+    }
+    let has_pressed = rule.additional_keys().any(|key| key.flags & KEY_BREAK == 0);
+    if !rule.remapped_key.is_empty() {
+        output.push(format_dsl_key(rule.remapped_key));
+    } else if has_pressed || (output.is_empty() && !rule.remap_vivaldi_to_fn_keys) {
+        output.push("None".to_string());
+    }
+    if rule.remap_vivaldi_to_fn_keys {
+        output.push("Fn".to_string());
+    }
+
+    let key = format_dsl_key(rule.original_key);
+    if trigger.is_empty() {
+        format!("{} => {}", key, output.join(" + "))
+    } else {
+        format!("{} + {} => {}", trigger.join(" "), key, output.join(" + "))
+    }
+}
+
+fn format_dsl_key(key: RemapCfgKey) -> String {
+    format_key(key).unwrap_or_else(|| format!("0x{:02X}", { key.make_code }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> RemapCfgKey {
+        parse_key(name).unwrap()
+    }
+
+    fn one_rule(line: &str) -> Rule {
+        let rules = parse_rule(line).unwrap();
+        assert_eq!(rules.len(), 1, "{}", line);
+        rules[0]
+    }
+
+    #[test]
+    fn outputs_become_additional_keys_in_order() {
+        let rule = one_rule("Ctrl !Shift + Vivaldi.Overview => release LCtrl + LWin + Tab");
+        assert_eq!(rule.left_ctrl, KeyState::Enforce);
+        assert_eq!(rule.left_shift, KeyState::EnforceNot);
+        assert_eq!(rule.original_key, key("Vivaldi.Overview"));
+        assert_eq!(rule.remapped_key, key("Tab"));
+        assert_eq!(rule.additional_keys().copied().collect::<Vec<_>>(), [key("LCtrl Break"), key("LWin")]);
+    }
+
+    #[test]
+    fn fn_and_none_outputs() {
+        let rule = one_rule("!Ctrl + Vivaldi.Back => Fn");
+        assert!(rule.remap_vivaldi_to_fn_keys);
+        assert!(rule.remapped_key.is_empty());
+
+        let rule = one_rule("Lock => LWin + None");
+        assert!(rule.remapped_key.is_empty());
+        assert_eq!(rule.additional_keys[0], key("LWin"));
+    }
+
+    #[test]
+    fn decompile_writes_header_and_conditions() {
+        let text = "set flip_search_and_assistant_on_pixelbook = false\n\
+                    set has_assistant_key = Disable\n\
+                    set is_non_chrome_ec = 5\n\
+                    Ctrl=3 RShift + Left => release RShift + Home\n\
+                    Lock => LWin + None\n";
+        let config = compile(text).unwrap();
+        assert_eq!(config.header.is_non_chrome_ec, RemapOverride::Unknown(5));
+        let decompiled = decompile(&config);
+        assert!(decompiled.contains("set has_assistant_key = Disable\n"));
+        assert!(decompiled.contains("set is_non_chrome_ec = 5\n"));
+        assert!(decompiled.contains("LCtrl=3 RShift + Left => release RShift + Home"));
+        assert!(decompiled.contains("Lock => LWin + None"));
+        assert_eq!(compile(&decompiled).unwrap(), config);
+    }
+
+    #[test]
+    fn decompile_leaves_out_keys_after_an_empty_slot() {
+        let mut rule = one_rule("Ctrl + Left => release LCtrl + Home");
+        rule.additional_keys[2] = key("LWin");
+        let config = Config::new(Header::default(), vec![rule]);
+        let binary = Config::from_bytes(&config.to_bytes()).unwrap();
+
+        let text = decompile(&binary);
+        assert!(text.contains("LCtrl + Left => release LCtrl + Home"), "{}", text);
+        let recompiled = Config::from_bytes(&compile(&text).unwrap().to_bytes()).unwrap();
+        let injected = |config: &Config| config.entries[0].additional_keys().copied().collect::<Vec<_>>();
+        assert_eq!(injected(&recompiled), injected(&binary));
+        assert_eq!(injected(&recompiled), [key("LCtrl Break")]);
+        assert!(recompiled.entries[0].additional_keys[2].is_empty());
+    }
+
+    #[test]
+    fn at_most_eight_additional_keys() {
+        assert!(compile("Ctrl + Left => A + B + C + D + E + F + G + H + Home").is_ok());
+        match compile("Ctrl + Left => A + B + C + D + E + F + G + H + I + Home") {
+            Err(Error::Syntax { line: 1, message }) => assert!(message.starts_with("9 additional keys"), "{}", message),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn syntax_errors_give_the_line_number() {
+        let text = "# comment\n\nCtrl + Left => Home\nCtrl + Left -> Home\n";
+        assert!(matches!(compile(text), Err(Error::Syntax { line: 4, .. })));
+    }
+
+    #[test]
+    fn unknown_names_are_syntax_errors() {
+        for (text, message) in [
+            ("Hyper + Left => Home", "unknown modifier 'Hyper'"),
+            ("Ctrl + Nowhere => Home", "Nowhere"),
+            ("set volume = 11", "unknown setting 'volume'"),
+            ("set has_assistant_key = Maybe", "expected AutoDetect, Enable or Disable"),
+        ] {
+            match compile(text) {
+                Err(Error::Syntax { line: 1, message: m }) => assert!(m.contains(message), "{}: {}", text, m),
+                other => panic!("{}: {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn release_modifiers_releases_each_trigger_once() {
+        let rule = one_rule("Ctrl Alt + Left => release modifiers + Home");
+        assert_eq!(rule.additional_keys().copied().collect::<Vec<_>>(), [key("LCtrl Break"), key("LAlt Break")]);
+
+        let rule = one_rule("Ctrl Alt + Left => release LAlt + release modifiers + Home");
+        assert_eq!(rule.additional_keys().copied().collect::<Vec<_>>(), [key("LAlt Break"), key("LCtrl Break")]);
+    }
+
+    #[test]
+    fn ensure_splits_on_the_modifier() {
+        let rules = parse_rule("Lock => ensure LWin + L").unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].search, KeyState::EnforceNot);
+        assert_eq!(rules[0].additional_keys().copied().collect::<Vec<_>>(), [key("LWin")]);
+        assert_eq!(rules[1].search, KeyState::Enforce);
+        assert_eq!(rules[1].additional_keys().count(), 0);
+        assert!(rules.iter().all(|rule| rule.remapped_key == key("L")));
+
+        let rule = one_rule("Search Ctrl + Lock => release modifiers + ensure LWin + L");
+        assert_eq!(rule.additional_keys().copied().collect::<Vec<_>>(), [key("LCtrl Break")]);
+
+        assert!(matches!(parse_rule("Lock => ensure A + L"), Err(message) if message.contains("modifier")));
+    }
+
+    #[test]
+    fn any_conditions_expand_per_side() {
+        use KeyState::{Enforce, EnforceNot, NoDetect};
+        let states = |rules: &[Rule]| rules.iter().map(|rule| (rule.left_ctrl, rule.right_ctrl)).collect::<Vec<_>>();
+
+        let rules = parse_rule("AnyCtrl + Left => Home").unwrap();
+        assert_eq!(states(&rules), [(Enforce, NoDetect), (EnforceNot, Enforce)]);
+
+        let rules = parse_rule("AnyCtrl + Left => release modifiers + Home").unwrap();
+        assert_eq!(states(&rules), [(Enforce, EnforceNot), (EnforceNot, Enforce), (Enforce, Enforce)]);
+        let releases: Vec<Vec<RemapCfgKey>> =
+            rules.iter().map(|rule| rule.additional_keys().copied().collect()).collect();
+        assert_eq!(
+            releases,
+            [vec![key("LCtrl Break")], vec![key("RCtrl Break")], vec![key("LCtrl Break"), key("RCtrl Break")]]
+        );
+
+        let rule = one_rule("!AnyCtrl + Left => release LCtrl + Home");
+        assert_eq!((rule.left_ctrl, rule.right_ctrl), (EnforceNot, EnforceNot));
+        assert_eq!(rule.additional_keys[0], key("LCtrl Break"));
+
+        assert!(parse_rule("AnyCtrl LCtrl + Left => Home").is_err());
+    }
+
+    #[test]
+    fn side_release_with_any_releases_the_held_sides() {
+        let rules = parse_rule("AnyCtrl + Backspace => release LCtrl + Delete").unwrap();
+        let entries: Vec<(KeyState, KeyState, Vec<RemapCfgKey>)> = rules
+            .iter()
+            .map(|rule| (rule.left_ctrl, rule.right_ctrl, rule.additional_keys().copied().collect()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (KeyState::Enforce, KeyState::EnforceNot, vec![key("LCtrl Break")]),
+                (KeyState::EnforceNot, KeyState::Enforce, vec![key("RCtrl Break")]),
+                (KeyState::Enforce, KeyState::Enforce, vec![key("LCtrl Break"), key("RCtrl Break")]),
+            ]
+        );
+
+        let rules = parse_rule("AnyCtrl Alt + Left => release RCtrl + release modifiers + Home").unwrap();
+        assert_eq!(rules[1].additional_keys().copied().collect::<Vec<_>>(), [key("RCtrl Break"), key("LAlt Break")]);
+
+        assert!(parse_rule("AnyCtrl + Left => release LCtrl + ensure RCtrl + Home").is_err());
+    }
+
+    #[test]
+    fn release_modifiers_on_search_waits_for_a_pressed_key() {
+        let rule = one_rule("Search + C => release modifiers + LCtrl + C");
+        assert_eq!(rule.additional_keys().copied().collect::<Vec<_>>(), [key("LCtrl"), key("LWin Break")]);
+        assert!(!rule.releases_search_first());
+        assert!(format_rule(&rule).ends_with("=> LCtrl + release LWin + C"));
+
+        let rule = one_rule("Search Ctrl + C => release modifiers + LAlt + C");
+        assert_eq!(
+            rule.additional_keys().copied().collect::<Vec<_>>(),
+            [key("LCtrl Break"), key("LAlt"), key("LWin Break")]
+        );
+
+        let config = compile("Search + C => release modifiers + LCtrl + C").unwrap();
+        let mut simulator = crate::simulator::Simulator::new(&config);
+        simulator.process(key("LWin"));
+        assert_eq!(simulator.process(key("C")), [key("LCtrl"), key("LWin Break"), key("C")]);
+
+        // nothing to wait for; lint reports it
+        let rule = one_rule("Search + Left => release modifiers + Home");
+        assert_eq!(rule.additional_keys().copied().collect::<Vec<_>>(), [key("LWin Break")]);
+        assert!(rule.releases_search_first());
+    }
+}
@@ -0,0 +1,196 @@
+//! Behavioural equivalence of two configs.
+//!
+//! Two configs are equivalent when every original key of either one produces
+//! the same events on press and release under every modifier state the driver
+//! can observe, and the header settings agree. Entry order, entry indices and
+//! redundant entries do not matter. A key that an `Fn` entry maps is compared
+//! at every top-row position, so the result holds for every board.
+
+use std::fmt;
+
+use crate::config::{Config, ModifierSet, RemapCfgKey};
+use crate::explain::{explain_with_top_row, Explanation};
+use crate::keys::FUNCTION_KEYS;
+use crate::lint::possible_states;
+use crate::simulator::{format_event, KeyEvent};
+
+/// A key and modifier state on which the two configs behave differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Counterexample {
+    pub left: Explanation,
+    pub right: Explanation,
+    /// Where the key sat on the top row, from 0 for the key sending F1, or
+    /// `None` when it was not on the top row.
+    pub top_row_position: Option<usize>,
+}
+
+impl Counterexample {
+    pub fn key(&self) -> RemapCfgKey {
+        self.left.key
+    }
+
+    pub fn held(&self) -> ModifierSet {
+        self.left.held
+    }
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.top_row_position {
+            Some(i) => writeln!(f, "{} as top-row key {} with {} held:", self.key(), i + 1, self.held())?,
+            None => writeln!(f, "{} with {} held:", self.key(), self.held())?,
+        }
+        writeln!(f, "  left:  {}", describe(&self.left))?;
+        write!(f, "  right: {}", describe(&self.right))
+    }
+}
+
+fn describe(explanation: &Explanation) -> String {
+    let events = |events: &[KeyEvent]| events.iter().map(|&e| format_event(e)).collect::<Vec<_>>().join(" ");
+    let source = match explanation.entry {
+        Some(i) => format!("config {}", i),
+        None => "pass-through".to_string(),
+    };
+    format!("{} / {} ({})", events(&explanation.press), events(&explanation.release), source)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Equivalence {
+    /// Header settings that differ, as `name: left -> right`.
+    pub header: Vec<String>,
+    pub counterexamples: Vec<Counterexample>,
+}
+
+impl Equivalence {
+    pub fn is_equivalent(&self) -> bool {
+        self.header.is_empty() && self.counterexamples.is_empty()
+    }
+}
+
+/// Compares two configs over every original key of either and every modifier
+/// state either header allows, collecting all counterexamples. A key mapped by
+/// an `Fn` entry in either config is also compared at every top-row position,
+/// reporting the first position where the configs differ.
+pub fn check_equivalence(left: &Config, right: &Config) -> Equivalence {
+    let mut result = Equivalence::default();
+
+    let (l, r) = (&left.header, &right.header);
+    if l.flip_search_and_assistant_on_pixelbook != r.flip_search_and_assistant_on_pixelbook {
+        result.header.push(format!(
+            "flip_search_and_assistant_on_pixelbook: {} -> {}",
+            l.flip_search_and_assistant_on_pixelbook, r.flip_search_and_assistant_on_pixelbook
+        ));
+    }
+    if l.has_assistant_key != r.has_assistant_key {
+        result.header.push(format!("has_assistant_key: {} -> {}", l.has_assistant_key, r.has_assistant_key));
+    }
+    if l.is_non_chrome_ec != r.is_non_chrome_ec {
+        result.header.push(format!("is_non_chrome_ec: {} -> {}", l.is_non_chrome_ec, r.is_non_chrome_ec));
+    }
+
+    let mut states = possible_states(left);
+    states.extend(possible_states(right));
+    states.sort();
+    states.dedup();
+
+    let mut keys = left.original_keys();
+    for key in right.original_keys() {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    for key in keys.into_iter().filter(|key| !key.is_empty()) {
+        let mut positions = vec![None];
+        if maps_to_fn(left, key) || maps_to_fn(right, key) {
+            positions.extend((0..FUNCTION_KEYS.len()).map(Some));
+        }
+        for &held in &states {
+            let counterexample = positions.iter().find_map(|&position| {
+                let top_row = top_row_with(key, position);
+                let a = explain_with_top_row(left, top_row.clone(), key, held);
+                let b = explain_with_top_row(right, top_row, key, held);
+                (a.press != b.press || a.release != b.release)
+                    .then_some(Counterexample { left: a, right: b, top_row_position: position })
+            });
+            result.counterexamples.extend(counterexample);
+        }
+    }
+    result
+}
+
+fn maps_to_fn(config: &Config, key: RemapCfgKey) -> bool {
+    config.entries.iter().any(|rule| rule.remap_vivaldi_to_fn_keys && rule.matches_key(key))
+}
+
+// a top row with `key` at `position` and no other keys that matter
+fn top_row_with(key: RemapCfgKey, position: Option<usize>) -> Vec<RemapCfgKey> {
+    let mut top_row = Vec::new();
+    if let Some(i) = position {
+        top_row.resize(i, RemapCfgKey::default());
+        top_row.push(key);
+    }
+    top_row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::compile;
+    use crate::keys::parse_key;
+
+    fn check(left: &str, right: &str) -> Equivalence {
+        check_equivalence(&compile(left).unwrap(), &compile(right).unwrap())
+    }
+
+    #[test]
+    fn reference_is_equivalent_to_itself() {
+        let config = crate::default_config();
+        assert!(check_equivalence(&config, &config).is_equivalent());
+    }
+
+    #[test]
+    fn order_and_redundant_entries_do_not_matter() {
+        let left = "Ctrl + Left => release LCtrl + Home\nShift + Left => End\n";
+        let right = "Shift !Ctrl + Left => End\nCtrl + Left => release LCtrl + Home\nCtrl Alt + Left => A\n";
+        assert!(check(left, right).is_equivalent());
+    }
+
+    #[test]
+    fn differing_output_is_a_counterexample() {
+        let result = check("Ctrl + Left => Home", "Ctrl + Left => End");
+        assert_eq!(result.counterexamples.len(), 128);
+        let counterexample = &result.counterexamples[0];
+        assert_eq!(counterexample.key(), parse_key("Left").unwrap());
+        assert!(counterexample.held().contains(crate::Modifier::LeftCtrl));
+        assert_eq!(counterexample.top_row_position, None);
+        assert_eq!(
+            counterexample.to_string(),
+            "Left with LCtrl held:\n  left:  +Home / -Home (config 0)\n  right: +End / -End (config 0)"
+        );
+    }
+
+    #[test]
+    fn header_differences_are_reported() {
+        let result = check("set has_assistant_key = Enable", "set has_assistant_key = Disable");
+        assert_eq!(result.header, ["has_assistant_key: Enable -> Disable"]);
+        assert!(!result.is_equivalent());
+    }
+
+    #[test]
+    fn fn_entries_off_the_classic_top_row_are_compared() {
+        let result = check("!Ctrl + Vivaldi.Snapshot => Fn", "");
+        assert!(!result.is_equivalent());
+        let counterexample = &result.counterexamples[0];
+        assert_eq!(counterexample.top_row_position, Some(0));
+        assert_eq!(counterexample.left.press, [parse_key("F1").unwrap()]);
+        assert!(counterexample.to_string().starts_with("Vivaldi.Snapshot as top-row key 1 with none held:"));
+    }
+
+    #[test]
+    fn fn_entries_match_a_remapped_key_only_off_the_top_row() {
+        let result = check("Vivaldi.Back => Fn", "Vivaldi.Back => Vivaldi.Back");
+        assert!(result.counterexamples.iter().all(|c| c.top_row_position == Some(0)));
+        assert!(!result.is_equivalent());
+    }
+}
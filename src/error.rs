@@ -0,0 +1,148 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::{CFG_MAGIC, HEADER_SIZE};
+
+/// Errors produced while reading, converting or writing a settings file.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The first four bytes are not `CFG_MAGIC`.
+    BadMagic { found: u32 },
+    /// The data ends before the 17-byte header does.
+    TruncatedHeader { len: usize },
+    /// The header declares more entries than the data contains.
+    SizeMismatch { remappings: u32, expected: usize, actual: usize },
+    /// A modifier condition holds a value other than 0, 1 or 2.
+    UnknownKeyState { entry: usize, field: &'static str, value: i32, offset: usize },
+    /// A header override holds a value other than 0, 1 or 2.
+    UnknownOverride { field: &'static str, value: i32, offset: usize },
+    /// A key string that is neither a known name nor a `0xNN` make code.
+    InvalidKey { text: String },
+    /// A modifier name that is not one of the eight `RemapCfg` conditions.
+    UnknownModifier { name: String },
+    /// A hex byte string with an odd number of digits or a non-hex digit.
+    InvalidHex { text: String },
+    /// A line of rule text that could not be parsed.
+    Syntax { line: usize, message: String },
+    /// An entry whose options cannot be applied to its conditions.
+    InvalidEntry { entry: usize, message: String },
+    /// An entry injects more keys than the additional_keys slots hold.
+    TooManyAdditionalKeys { entry: usize, count: usize, max: usize },
+    /// More entries than the `remappings` count allows.
+    TooManyEntries { count: usize, max: usize },
+    /// A preset name that is not in `PRESETS`.
+    UnknownPreset { name: String },
+    /// A board name that is not in the bundled board list.
+    UnknownBoard { name: String },
+    /// Neither a top-row physmap nor a known DMI name was found in a sysfs tree.
+    BoardNotDetected { root: PathBuf, product: Option<String> },
+    /// The directory an install would write to does not exist.
+    TargetNotFound { path: PathBuf },
+    /// A rollback found no backup of the target.
+    NoBackup { target: PathBuf },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Json(e) => write!(f, "Invalid JSON: {}", e),
+            Error::BadMagic { found } => write!(
+                f,
+                "Invalid magic number at offset 0x0000: expected 0x{:08X}, got 0x{:08X} ('{}')",
+                CFG_MAGIC,
+                found,
+                String::from_utf8_lossy(&found.to_le_bytes())
+            ),
+            Error::TruncatedHeader { len } => write!(
+                f,
+                "File too small (need at least {} bytes for header, got {})",
+                HEADER_SIZE, len
+            ),
+            Error::SizeMismatch { remappings, expected, actual } => write!(
+                f,
+                "File size mismatch: header declares {} remappings ({} bytes), got {} bytes",
+                remappings, expected, actual
+            ),
+            Error::UnknownKeyState { entry, field, value, offset } => write!(
+                f,
+                "Unknown key state {} in {} of config {} at offset 0x{:04X}",
+                value, field, entry, offset
+            ),
+            Error::UnknownOverride { field, value, offset } => write!(
+                f,
+                "Unknown override value {} in {} at offset 0x{:04X}",
+                value, field, offset
+            ),
+            Error::InvalidKey { text } => write!(
+                f,
+                "Invalid key '{}' (expected a key name such as 'Vivaldi.Back' or a make code such as 'E0 0x53')",
+                text
+            ),
+            Error::UnknownModifier { name } => write!(
+                f,
+                "Unknown modifier '{}' (expected LCtrl, LAlt, Search, Assistant, LShift, RCtrl, RAlt or RShift)",
+                name
+            ),
+            Error::InvalidHex { text } => write!(f, "Invalid hex bytes '{}'", text),
+            Error::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+            Error::InvalidEntry { entry, message } => write!(f, "Config {}: {}", entry, message),
+            Error::TooManyAdditionalKeys { entry, count, max } => write!(
+                f,
+                "Config {} has {} additional keys, at most {} fit in one entry",
+                entry, count, max
+            ),
+            Error::TooManyEntries { count, max } => {
+                write!(f, "Too many configurations ({}, max {})", count, max)
+            }
+            Error::UnknownPreset { name } => {
+                let names: Vec<&str> = crate::presets::PRESETS.iter().map(|preset| preset.name).collect();
+                write!(f, "Unknown preset '{}' (available: {})", name, names.join(", "))
+            }
+            Error::UnknownBoard { name } => {
+                let names: Vec<String> = crate::boards::boards().into_iter().map(|board| board.name).collect();
+                write!(f, "Unknown board '{}' (known: {}; use --top-row for others)", name, names.join(", "))
+            }
+            Error::BoardNotDetected { root, product } => {
+                write!(f, "No function_row_physmap under {}", root.display())?;
+                match product {
+                    Some(product) => write!(f, " and '{}' is not a known board", product),
+                    None => write!(f, " and no DMI product name"),
+                }
+            }
+            Error::TargetNotFound { path } => write!(
+                f,
+                "Cannot install to {}: the directory does not exist (is the root correct?)",
+                path.display()
+            ),
+            Error::NoBackup { target } => write!(f, "No backup of {} to roll back to", target.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
@@ -0,0 +1,137 @@
+//! Answers "what happens when I press this key with these modifiers held?".
+
+use std::fmt;
+
+use crate::config::{key_identity, Config, ModifierSet, RemapCfgKey};
+use crate::dsl::format_rule;
+use crate::keys::KEY_BREAK;
+use crate::simulator::{format_event, KeyEvent, Simulator};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub key: RemapCfgKey,
+    pub held: ModifierSet,
+    /// Index of the entry the driver applies, if any.
+    pub entry: Option<usize>,
+    /// Events sent when the key is pressed.
+    pub press: Vec<KeyEvent>,
+    /// Events sent when the key is released again, modifiers still held.
+    pub release: Vec<KeyEvent>,
+    /// Later entries that also match but never get the chance.
+    pub shadowed: Vec<usize>,
+    rules: Vec<(usize, String)>,
+}
+
+/// Explains `key` pressed with `held` modifiers, using the classic top row.
+pub fn explain(config: &Config, key: RemapCfgKey, held: ModifierSet) -> Explanation {
+    explain_with(Simulator::new(config), config, key, held)
+}
+
+/// Explains `key` pressed with `held` modifiers on a board whose top row
+/// sends `top_row`, left to right.
+pub fn explain_with_top_row(
+    config: &Config,
+    top_row: Vec<RemapCfgKey>,
+    key: RemapCfgKey,
+    held: ModifierSet,
+) -> Explanation {
+    explain_with(Simulator::with_top_row(config, top_row), config, key, held)
+}
+
+fn explain_with(mut simulator: Simulator<'_>, config: &Config, key: RemapCfgKey, held: ModifierSet) -> Explanation {
+    let key = key_identity(key);
+    for modifier in held.iter() {
+        simulator.process(modifier.key());
+    }
+
+    let press = simulator.process(key);
+    let entry = simulator.active_entry(key);
+    let release = simulator.process(RemapCfgKey::with_values(key.make_code, key.flags | KEY_BREAK));
+
+    let shadowed: Vec<usize> = match entry {
+        Some(first) => config.entries[first + 1..]
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.matches_key(key) && rule.matches_modifiers(held))
+            .map(|(i, _)| first + 1 + i)
+            .collect(),
+        None => Vec::new(),
+    };
+    let rules = entry
+        .iter()
+        .chain(&shadowed)
+        .map(|&i| (i, format_rule(&config.entries[i])))
+        .collect();
+
+    Explanation { key, held, entry, press, release, shadowed, rules }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = |i: usize| self.rules.iter().find(|(j, _)| *j == i).map_or("", |(_, text)| text.as_str());
+        let events = |events: &[KeyEvent]| events.iter().map(|&e| format_event(e)).collect::<Vec<_>>().join(", ");
+
+        writeln!(f, "{} with {} held", self.key, self.held)?;
+        match self.entry {
+            Some(i) => writeln!(f, "  fires config {}: {}", i, rule(i))?,
+            None => writeln!(f, "  no entry matches; the key passes through")?,
+        }
+        writeln!(f, "  press:   {}", events(&self.press))?;
+        writeln!(f, "  release: {}", events(&self.release))?;
+        for &i in &self.shadowed {
+            writeln!(f, "  shadows config {}: {}", i, rule(i))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::compile;
+    use crate::keys::parse_key;
+
+    #[test]
+    fn explains_the_reference_layout() {
+        let config = crate::default_config();
+        let held: ModifierSet = "Ctrl+Shift".parse().unwrap();
+        let explanation = explain(&config, parse_key("Vivaldi.Fullscreen").unwrap(), held);
+        assert_eq!(explanation.entry, Some(22));
+        assert!(explanation.shadowed.is_empty());
+        assert_eq!(
+            explanation.to_string(),
+            "Vivaldi.Fullscreen with LCtrl+LShift held\n\
+             \x20 fires config 22: LCtrl !Search LShift + Vivaldi.Fullscreen => release LCtrl + release LShift + LWin + P\n\
+             \x20 press:   -LCtrl, -LShift, +LWin, +P\n\
+             \x20 release: -P, -LWin, +LShift, +LCtrl\n"
+        );
+    }
+
+    #[test]
+    fn lists_shadowed_entries() {
+        let config = compile("Ctrl + Left => Home\nLeft => End\nShift + Left => PageUp\n").unwrap();
+        let held: ModifierSet = "Ctrl+Shift".parse().unwrap();
+        let explanation = explain(&config, parse_key("Left").unwrap(), held);
+        assert_eq!(explanation.entry, Some(0));
+        assert_eq!(explanation.shadowed, [1, 2]);
+        assert!(explanation.to_string().contains("  shadows config 2: LShift + Left => PageUp\n"));
+    }
+
+    #[test]
+    fn unmatched_key_passes_through() {
+        let config = compile("Ctrl + Left => Home").unwrap();
+        let explanation = explain(&config, parse_key("Left").unwrap(), ModifierSet::EMPTY);
+        assert_eq!(explanation.entry, None);
+        assert_eq!(explanation.press, [parse_key("Left").unwrap()]);
+        assert!(explanation.to_string().contains("no entry matches; the key passes through"));
+    }
+
+    #[test]
+    fn top_row_decides_the_function_key() {
+        let config = compile("Vivaldi.Snapshot => Fn").unwrap();
+        let key = parse_key("Vivaldi.Snapshot").unwrap();
+        let top_row = crate::board("jinlon").unwrap().top_row;
+        let explanation = explain_with_top_row(&config, top_row, key, ModifierSet::EMPTY);
+        assert_eq!(explanation.press, [parse_key("F5").unwrap()]);
+    }
+}
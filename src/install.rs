@@ -0,0 +1,481 @@
+//! Deploys a settings file to the driver's location, with backups.
+//!
+//! The target is `CONFIG_PATH` resolved under a configurable root, so the same
+//! code installs to `C:\` on Windows or to any directory that mirrors
+//! `Windows\System32\drivers`. The new file is validated, written to a
+//! temporary file next to the target and renamed over it, so the driver never
+//! sees a half-written file. The previous file is kept as a timestamped backup
+//! and only the newest few backups are kept.
+//!
+//! From Linux, `find_windows_roots` locates mounted Windows partitions. NTFS
+//! is case-insensitive but Linux paths are not, so every path component below
+//! the root is looked up ignoring case.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::keys::CONFIG_PATH;
+
+/// Where removable and manually mounted partitions usually appear on Linux.
+pub const MOUNT_BASES: [&str; 3] = ["/mnt", "/media", "/run/media"];
+
+/// Backups kept by default.
+pub const DEFAULT_KEEP_BACKUPS: usize = 5;
+
+const BACKUP_SUFFIX: &str = ".bak";
+const UNDO_SUFFIX: &str = ".undo";
+
+#[derive(Debug, Clone)]
+pub struct Installer {
+    root: PathBuf,
+    keep_backups: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallOutcome {
+    pub target: PathBuf,
+    /// Where the replaced file was saved, if there was one.
+    pub backup: Option<PathBuf>,
+    /// Old backups deleted to stay within the limit.
+    pub removed_backups: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollbackOutcome {
+    /// The backup that was restored and removed.
+    pub restored: PathBuf,
+    /// Where the replaced file was saved, if there was one. It is not a
+    /// backup, so a later rollback goes on to the next older one.
+    pub undo: Option<PathBuf>,
+}
+
+impl Installer {
+    /// An installer for the Windows installation rooted at `root`, e.g. `C:\`
+    /// or a mounted or fake tree containing `Windows/System32/drivers`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), keep_backups: DEFAULT_KEEP_BACKUPS }
+    }
+
+    pub fn keep_backups(mut self, count: usize) -> Self {
+        self.keep_backups = count;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// `CONFIG_PATH` below the root, matching existing components ignoring case.
+    pub fn target(&self) -> PathBuf {
+        let relative = CONFIG_PATH.split_once(":\\").map_or(CONFIG_PATH, |(_, rest)| rest);
+        resolve_ignore_case(&self.root, relative.split('\\'))
+    }
+
+    /// Validates `data` and installs it, backing up the current file first.
+    pub fn install(&self, data: &[u8]) -> Result<InstallOutcome> {
+        Config::from_bytes(data)?.validate()?;
+
+        let target = self.target();
+        target_dir(&target)?;
+
+        let backup = if target.exists() {
+            let backup = self.backup_path(&target);
+            fs::copy(&target, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+        write_atomic(&target, data)?;
+        let removed_backups = self.rotate()?;
+        Ok(InstallOutcome { target, backup, removed_backups })
+    }
+
+    /// Backups of the target, oldest first.
+    pub fn backups(&self) -> Result<Vec<PathBuf>> {
+        let target = self.target();
+        let dir = target_dir(&target)?;
+        let prefix = format!("{}.", file_name(&target));
+
+        let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let name = file_name(path);
+                name.starts_with(&prefix) && name.ends_with(BACKUP_SUFFIX)
+            })
+            .collect();
+        // timestamps sort lexically, the counter after them numerically
+        backups.sort_by_key(|path| {
+            let name = file_name(path);
+            let stamp = &name[prefix.len()..name.len() - BACKUP_SUFFIX.len()];
+            match stamp.split_once('_') {
+                Some((stamp, n)) => (stamp.to_string(), n.parse().unwrap_or(0)),
+                None => (stamp.to_string(), 0u32),
+            }
+        });
+        Ok(backups)
+    }
+
+    /// Restores the newest backup over the target and removes it, so each
+    /// rollback goes one backup further back. The file it replaces is saved
+    /// as `<target>.undo`, overwriting the one an earlier rollback saved.
+    pub fn rollback(&self) -> Result<RollbackOutcome> {
+        let target = self.target();
+        let restored = self.backups()?.pop().ok_or_else(|| Error::NoBackup { target: target.clone() })?;
+
+        let data = fs::read(&restored)?;
+        Config::from_bytes(&data)?;
+        let undo = if target.exists() {
+            let undo = target.with_file_name(format!("{}{}", file_name(&target), UNDO_SUFFIX));
+            fs::copy(&target, &undo)?;
+            Some(undo)
+        } else {
+            None
+        };
+        write_atomic(&target, &data)?;
+        fs::remove_file(&restored)?;
+        Ok(RollbackOutcome { restored, undo })
+    }
+
+    fn backup_path(&self, target: &Path) -> PathBuf {
+        let stamp = timestamp(SystemTime::now());
+        let name = file_name(target);
+        let mut path = target.with_file_name(format!("{}.{}{}", name, stamp, BACKUP_SUFFIX));
+        // two installs within one second
+        let mut n = 1;
+        while path.exists() {
+            path = target.with_file_name(format!("{}.{}_{:02}{}", name, stamp, n, BACKUP_SUFFIX));
+            n += 1;
+        }
+        path
+    }
+
+    fn rotate(&self) -> Result<Vec<PathBuf>> {
+        let backups = self.backups()?;
+        let excess = backups.len().saturating_sub(self.keep_backups);
+        let removed: Vec<PathBuf> = backups.into_iter().take(excess).collect();
+        for path in &removed {
+            fs::remove_file(path)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Joins `parts` onto `base`, using the existing entry whose name matches a
+/// part ignoring case. Parts with no match are joined as written.
+pub fn resolve_ignore_case<'a>(base: &Path, parts: impl IntoIterator<Item = &'a str>) -> PathBuf {
+    let mut path = base.to_path_buf();
+    for part in parts {
+        let exact = path.join(part);
+        path = if exact.exists() {
+            exact
+        } else {
+            fs::read_dir(&path)
+                .ok()
+                .and_then(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .find(|entry| entry.file_name().to_string_lossy().eq_ignore_ascii_case(part))
+                        .map(|entry| entry.path())
+                })
+                .unwrap_or(exact)
+        };
+    }
+    path
+}
+
+/// Whether `dir` looks like the root of a Windows installation, i.e. holds
+/// `Windows/System32/drivers` in any case.
+pub fn is_windows_root(dir: &Path) -> bool {
+    resolve_ignore_case(dir, ["Windows", "System32", "drivers"]).is_dir()
+}
+
+/// Windows installations mounted at, or up to two levels below, any of
+/// `bases` (e.g. `/mnt/c`, `/media/user/OS`, `/run/media/user/Windows`).
+pub fn find_windows_roots(bases: &[impl AsRef<Path>]) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    let mut visit = |dir: &Path| {
+        if is_windows_root(dir) && !roots.iter().any(|root: &PathBuf| root == dir) {
+            roots.push(dir.to_path_buf());
+        }
+    };
+
+    for base in bases {
+        let base = base.as_ref();
+        visit(base);
+        for child in subdirs(base) {
+            visit(&child);
+            // a mounted Windows tree is not searched further
+            if !is_windows_root(&child) {
+                for grandchild in subdirs(&child) {
+                    visit(&grandchild);
+                }
+            }
+        }
+    }
+    roots
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.is_dir()).collect())
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+// the drivers directory must already exist; a missing one usually means the
+// root is wrong, and creating System32 below it would hide that
+fn target_dir(target: &Path) -> Result<&Path> {
+    match target.parent() {
+        Some(dir) if dir.is_dir() => Ok(dir),
+        _ => Err(Error::TargetNotFound { path: target.to_path_buf() }),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Writes `data` to a temporary file next to `target`, then renames it over
+/// `target`.
+pub fn write_atomic(target: &Path, data: &[u8]) -> Result<()> {
+    let temp = target.with_file_name(format!("{}.tmp", file_name(target)));
+    let result = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp, target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    Ok(result?)
+}
+
+// UTC `YYYYMMDD-HHMMSS`
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HEADER_SIZE;
+    use crate::demo::demo_config;
+    use crate::testing::TempDir;
+
+    // a Windows root with an empty drivers directory, named in `case`
+    fn fake_root(name: &str, case: [&str; 3]) -> TempDir {
+        let root = TempDir::new(name);
+        fs::create_dir_all(case.iter().fold(root.path().to_path_buf(), |path, part| path.join(part))).unwrap();
+        root
+    }
+
+    fn windows_root(name: &str) -> TempDir {
+        fake_root(name, ["Windows", "System32", "drivers"])
+    }
+
+    fn other_config() -> Vec<u8> {
+        crate::preset("minimal").unwrap().to_bytes()
+    }
+
+    #[test]
+    fn install_writes_the_target_without_leaving_a_temporary_file() {
+        let root = windows_root("install");
+        let installer = Installer::new(root.path());
+        let outcome = installer.install(&demo_config()).unwrap();
+
+        assert_eq!(outcome.target, root.path().join("Windows/System32/drivers/croskbsettings.bin"));
+        assert_eq!(fs::read(&outcome.target).unwrap(), demo_config());
+        assert_eq!(outcome.backup, None);
+        let drivers = fs::read_dir(outcome.target.parent().unwrap()).unwrap();
+        let names: Vec<_> = drivers.map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, ["croskbsettings.bin"]);
+    }
+
+    #[test]
+    fn install_backs_up_the_replaced_file() {
+        let root = windows_root("backup");
+        let installer = Installer::new(root.path());
+        installer.install(&demo_config()).unwrap();
+        let outcome = installer.install(&other_config()).unwrap();
+
+        let backup = outcome.backup.unwrap();
+        assert!(file_name(&backup).starts_with("croskbsettings.bin."));
+        assert!(file_name(&backup).ends_with(".bak"));
+        assert_eq!(fs::read(&backup).unwrap(), demo_config());
+        assert_eq!(fs::read(&outcome.target).unwrap(), other_config());
+        assert_eq!(installer.backups().unwrap(), [backup]);
+    }
+
+    #[test]
+    fn only_the_newest_backups_are_kept() {
+        let root = windows_root("rotate");
+        let installer = Installer::new(root.path()).keep_backups(2);
+        let mut backups = Vec::new();
+        for _ in 0..4 {
+            backups.extend(installer.install(&demo_config()).unwrap().backup);
+        }
+        assert_eq!(backups.len(), 3);
+        assert_eq!(installer.backups().unwrap(), backups[1..]);
+        assert!(!backups[0].exists());
+    }
+
+    #[test]
+    fn invalid_files_are_not_installed() {
+        let root = windows_root("invalid");
+        let installer = Installer::new(root.path());
+        installer.install(&demo_config()).unwrap();
+
+        // left_ctrl of the first entry
+        let mut unknown_state = demo_config();
+        unknown_state[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&7i32.to_le_bytes());
+        for data in [&b"not a settings file"[..], &demo_config()[..100], &unknown_state] {
+            assert!(installer.install(data).is_err());
+        }
+        assert_eq!(fs::read(installer.target()).unwrap(), demo_config());
+        assert!(installer.backups().unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_drivers_directory_is_an_error() {
+        let root = TempDir::new("missing");
+        let installer = Installer::new(root.path());
+        assert!(matches!(installer.install(&demo_config()), Err(Error::TargetNotFound { .. })));
+    }
+
+    #[test]
+    fn path_components_match_ignoring_case() {
+        let root = fake_root("case", ["WINDOWS", "system32", "Drivers"]);
+        let installer = Installer::new(root.path());
+        assert_eq!(installer.target(), root.path().join("WINDOWS/system32/Drivers/croskbsettings.bin"));
+        installer.install(&demo_config()).unwrap();
+        assert!(root.path().join("WINDOWS/system32/Drivers/croskbsettings.bin").is_file());
+        assert!(is_windows_root(root.path()));
+        assert_eq!(find_windows_roots(&[root.path()]), [root.path()]);
+    }
+
+    #[test]
+    fn missing_parts_are_joined_as_written() {
+        let root = fake_root("resolve", ["windows", "SYSTEM32", "Drivers"]);
+        assert_eq!(
+            resolve_ignore_case(root.path(), ["Windows", "System32", "drivers", "croskbsettings.bin"]),
+            root.path().join("windows/SYSTEM32/Drivers/croskbsettings.bin")
+        );
+        assert_eq!(resolve_ignore_case(root.path(), ["WINDOWS", "SysWOW64", "drivers"]), root.path().join("windows/SysWOW64/drivers"));
+        assert!(!is_windows_root(&root.path().join("windows")));
+    }
+
+    #[test]
+    fn windows_roots_are_found_up_to_two_levels_below_a_base() {
+        let mounts = TempDir::new("mounts");
+        let dir = |path: &str| {
+            let path = mounts.path().join(path);
+            fs::create_dir_all(&path).unwrap();
+            path
+        };
+        let c = dir("mnt/c");
+        dir("mnt/c/Windows/System32/drivers");
+        // a virtual machine image unpacked inside a mounted root
+        dir("mnt/c/vm/Windows/System32/drivers");
+        dir("mnt/data/photos");
+        let os = dir("run/media/alice/OS");
+        dir("run/media/alice/OS/WINDOWS/system32/DRIVERS");
+        dir("run/media/alice/backup/old/Windows/System32/drivers");
+        fs::write(mounts.path().join("run/media/alice/Windows"), b"not a directory").unwrap();
+
+        let bases = ["mnt", "media", "run/media"].map(|base| mounts.path().join(base));
+        assert_eq!(find_windows_roots(&bases), [c, os]);
+    }
+
+    #[test]
+    fn rollback_restores_the_newest_backup_and_saves_the_replaced_file() {
+        let root = windows_root("rollback");
+        let installer = Installer::new(root.path());
+        installer.install(&demo_config()).unwrap();
+        installer.install(&other_config()).unwrap();
+
+        let outcome = installer.rollback().unwrap();
+        assert!(!outcome.restored.exists());
+        assert_eq!(fs::read(installer.target()).unwrap(), demo_config());
+        let undo = outcome.undo.unwrap();
+        assert_eq!(undo, root.path().join("Windows/System32/drivers/croskbsettings.bin.undo"));
+        assert_eq!(fs::read(&undo).unwrap(), other_config());
+        assert!(installer.backups().unwrap().is_empty());
+        assert!(matches!(installer.rollback(), Err(Error::NoBackup { .. })));
+    }
+
+    #[test]
+    fn rollbacks_walk_back_through_the_backups() {
+        let root = windows_root("rollback-twice");
+        let installer = Installer::new(root.path());
+        let configs: Vec<Vec<u8>> =
+            ["default", "minimal", "plain-fkeys"].iter().map(|name| crate::preset(name).unwrap().to_bytes()).collect();
+        for data in &configs {
+            installer.install(data).unwrap();
+        }
+
+        installer.rollback().unwrap();
+        assert_eq!(fs::read(installer.target()).unwrap(), configs[1]);
+        let outcome = installer.rollback().unwrap();
+        assert_eq!(fs::read(installer.target()).unwrap(), configs[0]);
+        assert_eq!(fs::read(outcome.undo.unwrap()).unwrap(), configs[1]);
+        assert!(installer.backups().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rollback_without_a_backup_fails() {
+        let root = windows_root("no-backup");
+        let installer = Installer::new(root.path());
+        installer.install(&demo_config()).unwrap();
+        assert!(matches!(installer.rollback(), Err(Error::NoBackup { .. })));
+    }
+
+    #[test]
+    fn backups_from_one_second_sort_by_their_counter() {
+        let root = windows_root("counter");
+        let installer = Installer::new(root.path());
+        installer.install(&demo_config()).unwrap();
+        let drivers = root.path().join("Windows/System32/drivers");
+        let names = [
+            "croskbsettings.bin.20260101-000000.bak",
+            "croskbsettings.bin.20260101-000000_99.bak",
+            "croskbsettings.bin.20260101-000000_100.bak",
+            "croskbsettings.bin.20260102-000000.bak",
+        ];
+        for name in names.iter().rev() {
+            fs::write(drivers.join(name), demo_config()).unwrap();
+        }
+        let expected: Vec<PathBuf> = names.iter().map(|name| drivers.join(name)).collect();
+        assert_eq!(installer.backups().unwrap(), expected);
+
+        fs::remove_file(&expected[3]).unwrap();
+        assert_eq!(installer.rollback().unwrap().restored, expected[2]);
+    }
+}
@@ -0,0 +1,615 @@
+use std::fmt;
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::config::{
+    defer_search_release, AnyModifier, Config, Header, KeyState, Modifier, RemapCfgKey, RemapCfgKeyState, RemapCfgOverride, RemapOverride,
+    Rule, CFG_MAGIC, CFG_SIZE, HEADER_SIZE, MAX_ADDITIONAL_KEYS, MAX_ENTRIES,
+};
+use crate::error::{Error, Result};
+use crate::keys::{format_key, parse_key, KEY_BREAK, KEY_E0, K_LWIN};
+
+// json structures
+
+// KeyState and RemapOverride as read from JSON. `{"Unknown": 1}` is the value
+// the driver reads as Enforce, so an unknown value the enum defines is read as
+// that variant.
+#[derive(Deserialize)]
+#[serde(rename = "KeyState")]
+pub(crate) enum KeyStateJson {
+    NoDetect,
+    Enforce,
+    EnforceNot,
+    Unknown(RemapCfgKeyState),
+}
+
+impl From<KeyStateJson> for KeyState {
+    fn from(state: KeyStateJson) -> Self {
+        match state {
+            KeyStateJson::NoDetect => KeyState::NoDetect,
+            KeyStateJson::Enforce => KeyState::Enforce,
+            KeyStateJson::EnforceNot => KeyState::EnforceNot,
+            KeyStateJson::Unknown(value) => KeyState::from(value),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "RemapOverride")]
+pub(crate) enum RemapOverrideJson {
+    AutoDetect,
+    Enable,
+    Disable,
+    Unknown(RemapCfgOverride),
+}
+
+impl From<RemapOverrideJson> for RemapOverride {
+    fn from(value: RemapOverrideJson) -> Self {
+        match value {
+            RemapOverrideJson::AutoDetect => RemapOverride::AutoDetect,
+            RemapOverrideJson::Enable => RemapOverride::Enable,
+            RemapOverrideJson::Disable => RemapOverride::Disable,
+            RemapOverrideJson::Unknown(value) => RemapOverride::from(value),
+        }
+    }
+}
+
+/// A key in the JSON format. On input it may also be written as a single
+/// string such as `"Vivaldi.Back"`, `"LCtrl Break"` or `"E0 0x53"`; `name`,
+/// `make_code_hex` and `flags_decoded` are informational and ignored on input.
+#[derive(Debug, Serialize, Clone)]
+pub struct RemapCfgKeyJson {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    pub make_code: u16,
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub make_code_hex: String,
+    pub flags: u16,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub flags_decoded: Vec<String>,
+}
+
+impl RemapCfgKeyJson {
+    pub fn new(make_code: u16, flags: u16) -> Self {
+        let mut flags_decoded = Vec::new();
+        if flags & 0x0001 != 0 { flags_decoded.push("KEY_BREAK".to_string()); }
+        if flags & 0x0002 != 0 { flags_decoded.push("KEY_E0".to_string()); }
+        if flags & 0x0004 != 0 { flags_decoded.push("KEY_E1".to_string()); }
+        if flags & !0x0007 != 0 { flags_decoded.push(format!("0x{:04X}", flags & !0x0007)); }
+
+        Self {
+            name: format_key(RemapCfgKey::with_values(make_code, flags)).unwrap_or_default(),
+            make_code,
+            make_code_hex: format!("0x{:02X}", make_code),
+            flags,
+            flags_decoded,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.make_code == 0 && self.flags == 0
+    }
+
+    pub fn to_key(&self) -> RemapCfgKey {
+        RemapCfgKey::with_values(self.make_code, self.flags)
+    }
+}
+
+// the object form of RemapCfgKeyJson, as accepted on input
+#[derive(Deserialize)]
+struct RemapCfgKeyFields {
+    make_code: u16,
+    flags: u16,
+}
+
+impl<'de> Deserialize<'de> for RemapCfgKeyJson {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = RemapCfgKeyJson;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a key name string or an object with make_code and flags")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> std::result::Result<Self::Value, E> {
+                let key = parse_key(text).map_err(E::custom)?;
+                Ok(key.into())
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> std::result::Result<Self::Value, A::Error> {
+                let fields = RemapCfgKeyFields::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(RemapCfgKeyJson::new(fields.make_code, fields.flags))
+            }
+        }
+
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+impl From<RemapCfgKey> for RemapCfgKeyJson {
+    fn from(key: RemapCfgKey) -> Self {
+        Self::new(key.make_code, key.flags)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigEntryJson {
+    pub index: u32,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub left_ctrl: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub left_alt: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub search: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub assistant: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub left_shift: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub right_ctrl: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub right_alt: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub right_shift: KeyState,
+
+    pub original_key: RemapCfgKeyJson,
+    pub remap_vivaldi_to_fn: bool,
+    /// The stored byte, when it is neither 0 nor 1.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub remap_vivaldi_to_fn_byte: Option<u8>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub remapped_key: Option<RemapCfgKeyJson>,
+
+    /// Slots up to the last non-empty one; empty slots in between are kept
+    /// so the keys stay in place.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub additional_keys: Vec<RemapCfgKeyJson>,
+
+    /// Input only: `Enforce` needs the left or right key held, `EnforceNot`
+    /// neither. The entry is expanded into left and right conditions, one
+    /// entry per side held.
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub any_ctrl: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub any_alt: KeyState,
+    #[serde(skip_serializing_if = "KeyState::is_no_detect", default)]
+    pub any_shift: KeyState,
+
+    /// Input only: release every `Enforce`d modifier before the additional
+    /// keys, unless they already release it.
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub consume_trigger_modifiers: bool,
+
+    /// Input only: the output needs the Windows key. An open `search`
+    /// condition becomes two entries, one injecting LWin while Search is not
+    /// held and one relying on the held Search key.
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub output_requires_win: bool,
+}
+
+impl ConfigEntryJson {
+    pub fn from_rule(index: u32, rule: &Rule) -> Self {
+        Self {
+            index,
+            left_ctrl: rule.left_ctrl,
+            left_alt: rule.left_alt,
+            search: rule.search,
+            assistant: rule.assistant,
+            left_shift: rule.left_shift,
+            right_ctrl: rule.right_ctrl,
+            right_alt: rule.right_alt,
+            right_shift: rule.right_shift,
+            original_key: rule.original_key.into(),
+            remap_vivaldi_to_fn: rule.remap_vivaldi_to_fn_keys,
+            remap_vivaldi_to_fn_byte: rule.fn_keys_byte,
+            remapped_key: if rule.remapped_key.is_empty() { None } else { Some(rule.remapped_key.into()) },
+            additional_keys: {
+                let used = rule.additional_keys.iter().rposition(|key| !key.is_empty()).map_or(0, |i| i + 1);
+                rule.additional_keys[..used].iter().map(|&key| key.into()).collect()
+            },
+            any_ctrl: KeyState::NoDetect,
+            any_alt: KeyState::NoDetect,
+            any_shift: KeyState::NoDetect,
+            consume_trigger_modifiers: false,
+            output_requires_win: false,
+        }
+    }
+
+    /// Converts the entry at position `entry` of the configs list into the
+    /// entries it stands for: one, or several when `any_*` conditions or
+    /// `output_requires_win` split it.
+    pub fn to_rules(&self, entry: usize) -> Result<Vec<Rule>> {
+        let invalid = |message: String| Error::InvalidEntry { entry, message };
+        let mut cfg = Rule::new();
+
+        // parse modifier states
+        cfg.left_ctrl = self.left_ctrl;
+        cfg.left_alt = self.left_alt;
+        cfg.search = self.search;
+        cfg.assistant = self.assistant;
+        cfg.left_shift = self.left_shift;
+        cfg.right_ctrl = self.right_ctrl;
+        cfg.right_alt = self.right_alt;
+        cfg.right_shift = self.right_shift;
+
+        // parse original key
+        cfg.original_key = self.original_key.to_key();
+
+        // parse remap flag
+        cfg.remap_vivaldi_to_fn_keys = self.remap_vivaldi_to_fn;
+        cfg.fn_keys_byte = self.remap_vivaldi_to_fn_byte;
+
+        // parse remapped key
+        if let Some(ref remapped) = self.remapped_key {
+            cfg.remapped_key = remapped.to_key();
+        }
+
+        // expand side-agnostic conditions, then split on Search
+        let mut rules = vec![cfg];
+        for (any, state) in self.anys() {
+            let per_side = self.consume_trigger_modifiers || self.releases_side(any);
+            let mut expanded = Vec::new();
+            for rule in &rules {
+                expanded.extend(rule.expand_any(any, state, per_side).map_err(invalid)?);
+            }
+            rules = expanded;
+        }
+        if self.output_requires_win {
+            let mut expanded = Vec::new();
+            for rule in rules {
+                match rule.search {
+                    KeyState::NoDetect => {
+                        for search in [KeyState::EnforceNot, KeyState::Enforce] {
+                            let mut rule = rule;
+                            rule.search = search;
+                            expanded.push(rule);
+                        }
+                    }
+                    KeyState::Unknown(_) => {
+                        return Err(invalid(
+                            "output_requires_win needs search to be NoDetect, Enforce or EnforceNot".to_string(),
+                        ));
+                    }
+                    _ => expanded.push(rule),
+                }
+            }
+            rules = expanded;
+        }
+
+        rules.into_iter().map(|rule| self.fill_additional_keys(rule, entry)).collect()
+    }
+
+    fn anys(&self) -> [(AnyModifier, KeyState); 3] {
+        [
+            (AnyModifier::Ctrl, self.any_ctrl),
+            (AnyModifier::Alt, self.any_alt),
+            (AnyModifier::Shift, self.any_shift),
+        ]
+    }
+
+    // whether the written additional keys release either side of `any`
+    fn releases_side(&self, any: AnyModifier) -> bool {
+        self.additional_keys.iter().any(|key| any.is_side_release(key.to_key()))
+    }
+
+    // the written additional keys, after the trigger releases and before the
+    // Windows key when those are requested; a release of either side of an
+    // `Enforce`d `any_*` condition releases the sides the entry requires held
+    fn fill_additional_keys(&self, mut rule: Rule, entry: usize) -> Result<Rule> {
+        let win = RemapCfgKey::with_values(K_LWIN, KEY_E0);
+        let written: Vec<RemapCfgKey> = self.additional_keys.iter().map(RemapCfgKeyJson::to_key).collect();
+
+        let mut keys = Vec::new();
+        if self.consume_trigger_modifiers {
+            // a held Search key is the Windows key the output needs
+            let win_release = RemapCfgKey::with_values(K_LWIN, KEY_E0 | KEY_BREAK);
+            keys.extend(
+                rule.trigger_releases()
+                    .into_iter()
+                    .filter(|key| !written.contains(key))
+                    .filter(|&key| !self.output_requires_win || key != win_release),
+            );
+        }
+        keys.extend_from_slice(&written);
+        for (any, state) in self.anys() {
+            if state == KeyState::Enforce && self.releases_side(any) {
+                keys = rule.release_held_sides(any, &keys);
+            }
+        }
+        // a consumed Search key is released after the first pressed key
+        if self.consume_trigger_modifiers && !written.contains(&Modifier::Search.release()) {
+            defer_search_release(&mut keys);
+        }
+        if self.output_requires_win && rule.search == KeyState::EnforceNot && !written.contains(&win) {
+            keys.push(win);
+        }
+
+        if keys.len() > MAX_ADDITIONAL_KEYS {
+            return Err(Error::TooManyAdditionalKeys { entry, count: keys.len(), max: MAX_ADDITIONAL_KEYS });
+        }
+        for (slot, key) in rule.additional_keys.iter_mut().zip(keys) {
+            *slot = key;
+        }
+        Ok(rule)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigFileJson {
+    pub magic: String,
+    pub magic_hex: String,
+    pub valid: bool,
+    pub remappings: u32,
+    pub flip_search_and_assistant_on_pixelbook: bool,
+    /// The stored flip byte, when it is neither 0 nor 1.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub flip_search_and_assistant_on_pixelbook_byte: Option<u8>,
+    pub has_assistant_key: RemapOverride,
+    pub is_non_chrome_ec: RemapOverride,
+    pub file_size_bytes: usize,
+    pub expected_size_bytes: usize,
+    pub configs: Vec<ConfigEntryJson>,
+    /// Bytes after the last entry, as hex.
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub trailing_bytes_hex: String,
+}
+
+impl From<&Config> for ConfigFileJson {
+    fn from(config: &Config) -> Self {
+        let header = &config.header;
+        Self {
+            magic: String::from_utf8_lossy(&CFG_MAGIC.to_le_bytes()).to_string(),
+            magic_hex: format!("0x{:08X}", CFG_MAGIC),
+            valid: true,
+            remappings: header.remappings,
+            flip_search_and_assistant_on_pixelbook: header.flip_search_and_assistant_on_pixelbook,
+            flip_search_and_assistant_on_pixelbook_byte: header.flip_byte,
+            has_assistant_key: header.has_assistant_key,
+            is_non_chrome_ec: header.is_non_chrome_ec,
+            file_size_bytes: config.encoded_len(),
+            expected_size_bytes: HEADER_SIZE + header.remappings as usize * CFG_SIZE,
+            configs: config
+                .entries
+                .iter()
+                .enumerate()
+                .map(|(i, rule)| ConfigEntryJson::from_rule(i as u32, rule))
+                .collect(),
+            trailing_bytes_hex: config.trailing.iter().map(|b| format!("{:02X}", b)).collect(),
+        }
+    }
+}
+
+impl ConfigFileJson {
+    pub fn to_config(&self) -> Result<Config> {
+        let mut entries = Vec::new();
+        for (i, entry) in self.configs.iter().enumerate() {
+            entries.extend(entry.to_rules(i)?);
+        }
+        if entries.len() > MAX_ENTRIES {
+            return Err(Error::TooManyEntries { count: entries.len(), max: MAX_ENTRIES });
+        }
+
+        let header = Header {
+            remappings: entries.len() as u32,
+            flip_search_and_assistant_on_pixelbook: self.flip_search_and_assistant_on_pixelbook,
+            flip_byte: self.flip_search_and_assistant_on_pixelbook_byte,
+            has_assistant_key: self.has_assistant_key,
+            is_non_chrome_ec: self.is_non_chrome_ec,
+        };
+
+        let mut config = Config::new(header, entries);
+        config.trailing = decode_hex(&self.trailing_bytes_hex)?;
+        Ok(config)
+    }
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    let invalid = || Error::InvalidHex { text: hex.to_string() };
+    if !digits.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let text: String = pair.iter().collect();
+            u8::from_str_radix(&text, 16).map_err(|_| invalid())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{K_LALT, K_LCTRL};
+
+    const REFERENCE_JSON: &str = include_str!("../output.json");
+
+    fn entry(json: &str) -> ConfigEntryJson {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn keys_are_written_with_their_names() {
+        let key = serde_json::to_value(RemapCfgKeyJson::from(RemapCfgKey::with_values(0x4B, KEY_E0 | KEY_BREAK))).unwrap();
+        assert_eq!(key["name"], "Left Break");
+        assert_eq!(key["make_code_hex"], "0x4B");
+        assert_eq!(key["flags_decoded"], serde_json::json!(["KEY_BREAK", "KEY_E0"]));
+    }
+
+    #[test]
+    fn keys_are_read_as_names_or_objects() {
+        let keys: Vec<RemapCfgKeyJson> =
+            serde_json::from_str(r#"["Vivaldi.Back", "LCtrl Break", "E0 0x53", {"make_code": 29, "flags": 2}]"#).unwrap();
+        let keys: Vec<RemapCfgKey> = keys.iter().map(RemapCfgKeyJson::to_key).collect();
+        assert_eq!(
+            keys,
+            [
+                RemapCfgKey::with_values(0x6A, KEY_E0),
+                RemapCfgKey::with_values(0x1D, KEY_BREAK),
+                RemapCfgKey::with_values(0x53, KEY_E0),
+                RemapCfgKey::with_values(0x1D, KEY_E0),
+            ]
+        );
+        assert!(serde_json::from_str::<RemapCfgKeyJson>(r#""Nowhere""#).is_err());
+    }
+
+    #[test]
+    fn at_most_eight_additional_keys() {
+        let with_keys = |count: usize| {
+            let keys: Vec<String> = (0..count).map(|i| format!("\"0x{:02X}\"", 0x10 + i)).collect();
+            entry(&format!(
+                r#"{{"index": 0, "original_key": "Left", "remap_vivaldi_to_fn": false, "additional_keys": [{}]}}"#,
+                keys.join(", ")
+            ))
+        };
+        assert_eq!(with_keys(8).to_rules(3).unwrap()[0].additional_keys().count(), 8);
+        assert!(matches!(
+            with_keys(9).to_rules(3),
+            Err(Error::TooManyAdditionalKeys { entry: 3, count: 9, max: MAX_ADDITIONAL_KEYS })
+        ));
+    }
+
+    #[test]
+    fn unknown_state_with_a_defined_value_is_normalised() {
+        let entry = entry(r#"{"index": 0, "left_ctrl": {"Unknown": 1}, "left_alt": {"Unknown": 2},
+            "search": {"Unknown": 0}, "original_key": "Backspace", "remap_vivaldi_to_fn": false}"#);
+        assert_eq!(entry.left_ctrl, KeyState::Enforce);
+        assert_eq!(entry.left_alt, KeyState::EnforceNot);
+        assert_eq!(entry.search, KeyState::NoDetect);
+    }
+
+    #[test]
+    fn unknown_state_with_an_undefined_value_is_kept() {
+        let entry = entry(r#"{"index": 0, "left_ctrl": {"Unknown": 7}, "original_key": "Backspace",
+            "remap_vivaldi_to_fn": false}"#);
+        assert_eq!(entry.left_ctrl, KeyState::Unknown(7));
+        let rule = entry.to_rules(0).unwrap()[0];
+        assert_eq!(RemapCfgKeyState::from(rule.left_ctrl), 7);
+    }
+
+    #[test]
+    fn unknown_override_with_a_defined_value_is_normalised() {
+        let mut json: serde_json::Value = serde_json::from_str(REFERENCE_JSON).unwrap();
+        json["has_assistant_key"] = serde_json::json!({"Unknown": 2});
+        json["is_non_chrome_ec"] = serde_json::json!({"Unknown": 9});
+        let config = Config::from_json(&json.to_string()).unwrap();
+        assert_eq!(config.header.has_assistant_key, RemapOverride::Disable);
+        assert_eq!(config.header.is_non_chrome_ec, RemapOverride::Unknown(9));
+    }
+
+    #[test]
+    fn normalised_entry_matches_like_the_encoded_one() {
+        let json = r#"{"index": 0, "left_ctrl": {"Unknown": 1}, "original_key": "Backspace",
+            "remap_vivaldi_to_fn": false, "remapped_key": "Delete"}"#;
+        let rule = entry(json).to_rules(0).unwrap()[0];
+        let config = Config::new(Header::default(), vec![rule]);
+        let decoded = Config::from_bytes(&config.to_bytes()).unwrap();
+        assert_eq!(decoded.entries[0], rule);
+        assert!(!rule.matches_modifiers(crate::config::ModifierSet::EMPTY));
+    }
+
+    #[test]
+    fn consumed_triggers_come_before_the_written_keys() {
+        let keys = |json: &str| entry(json).to_rules(0).unwrap()[0].additional_keys().copied().collect::<Vec<_>>();
+        let lctrl_break = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
+        let lalt_break = RemapCfgKey::with_values(K_LALT, KEY_BREAK);
+
+        let consumed = keys(r#"{"index": 0, "left_ctrl": "Enforce", "left_alt": "Enforce", "original_key": "Left",
+            "remap_vivaldi_to_fn": false, "remapped_key": "Home", "consume_trigger_modifiers": true}"#);
+        assert_eq!(consumed, [lctrl_break, lalt_break]);
+
+        let written = keys(r#"{"index": 0, "left_ctrl": "Enforce", "left_alt": "Enforce", "original_key": "Left",
+            "remap_vivaldi_to_fn": false, "remapped_key": "Home", "additional_keys": ["LAlt Break"],
+            "consume_trigger_modifiers": true}"#);
+        assert_eq!(written, [lctrl_break, lalt_break]);
+    }
+
+    #[test]
+    fn output_requires_win_splits_on_search() {
+        let win = RemapCfgKey::with_values(K_LWIN, KEY_E0);
+        let rules = entry(r#"{"index": 0, "original_key": "Lock", "remap_vivaldi_to_fn": false,
+            "remapped_key": "L", "output_requires_win": true}"#)
+            .to_rules(0)
+            .unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].search, KeyState::EnforceNot);
+        assert_eq!(rules[0].additional_keys().copied().collect::<Vec<_>>(), [win]);
+        assert_eq!(rules[1].search, KeyState::Enforce);
+        assert_eq!(rules[1].additional_keys().count(), 0);
+
+        let held = entry(r#"{"index": 0, "search": "Enforce", "left_ctrl": "Enforce", "original_key": "Lock",
+            "remap_vivaldi_to_fn": false, "remapped_key": "L", "consume_trigger_modifiers": true,
+            "output_requires_win": true}"#)
+            .to_rules(0)
+            .unwrap();
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].additional_keys().copied().collect::<Vec<_>>(), [RemapCfgKey::with_values(K_LCTRL, KEY_BREAK)]);
+    }
+
+    #[test]
+    fn side_release_with_any_ctrl_releases_the_held_sides() {
+        let lctrl_break = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
+        let rctrl_break = RemapCfgKey::with_values(K_LCTRL, KEY_E0 | KEY_BREAK);
+        let rules = entry(r#"{"index": 0, "any_ctrl": "Enforce", "original_key": "Backspace",
+            "remap_vivaldi_to_fn": false, "remapped_key": "Delete", "additional_keys": ["LCtrl Break"]}"#)
+            .to_rules(0)
+            .unwrap();
+        let entries: Vec<(KeyState, KeyState, Vec<RemapCfgKey>)> = rules
+            .iter()
+            .map(|rule| (rule.left_ctrl, rule.right_ctrl, rule.additional_keys().copied().collect()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (KeyState::Enforce, KeyState::EnforceNot, vec![lctrl_break]),
+                (KeyState::EnforceNot, KeyState::Enforce, vec![rctrl_break]),
+                (KeyState::Enforce, KeyState::Enforce, vec![lctrl_break, rctrl_break]),
+            ]
+        );
+
+        let rules = entry(r#"{"index": 0, "any_ctrl": "EnforceNot", "original_key": "Backspace",
+            "remap_vivaldi_to_fn": false, "additional_keys": ["LCtrl Break"]}"#)
+            .to_rules(0)
+            .unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].additional_keys().copied().collect::<Vec<_>>(), [lctrl_break]);
+    }
+
+    #[test]
+    fn unknown_values_and_stored_bytes_survive_json() {
+        let mut config = Config::from_bytes(&crate::demo::demo_config()).unwrap();
+        config.header.flip_byte = Some(7);
+        config.header.is_non_chrome_ec = RemapOverride::Unknown(9);
+        config.entries[0].right_alt = KeyState::Unknown(5);
+        config.entries[1].fn_keys_byte = Some(2);
+        config.trailing = vec![1, 2, 3];
+        let data = config.to_bytes();
+
+        let decoded = Config::from_bytes(&data).unwrap();
+        let json = decoded.to_json().unwrap();
+        let from_json = Config::from_json(&json).unwrap();
+        assert_eq!(from_json, decoded);
+        assert_eq!(from_json.to_bytes(), data);
+        assert_eq!(from_json.header.flip_byte, Some(7));
+        assert_eq!(from_json.header.is_non_chrome_ec, RemapOverride::Unknown(9));
+        assert_eq!(from_json.entries[0].right_alt, KeyState::Unknown(5));
+        assert_eq!(from_json.entries[1].fn_keys_byte, Some(2));
+        assert_eq!(from_json.trailing, [1, 2, 3]);
+    }
+
+    #[test]
+    fn consumed_search_is_released_after_the_first_pressed_key() {
+        let rule = entry(r#"{"index": 0, "search": "Enforce", "original_key": "C", "remap_vivaldi_to_fn": false,
+            "remapped_key": "C", "additional_keys": ["LCtrl"], "consume_trigger_modifiers": true}"#)
+            .to_rules(0)
+            .unwrap()[0];
+        let keys: Vec<RemapCfgKey> = rule.additional_keys().copied().collect();
+        assert_eq!(keys, [RemapCfgKey::with_values(K_LCTRL, 0), Modifier::Search.release()]);
+        assert!(!rule.releases_search_first());
+    }
+}
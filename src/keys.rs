@@ -0,0 +1,383 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::RemapCfgKey;
+use crate::error::Error;
+
+// config file location
+pub const CONFIG_PATH: &str = "C:\\Windows\\System32\\drivers\\croskbsettings.bin";
+
+// Keycodes and constants
+pub const K_LCTRL: u16 = 0x1D;
+pub const K_LALT: u16 = 0x38;
+pub const K_LSHFT: u16 = 0x2A;
+pub const K_LWIN: u16 = 0x5B;
+pub const K_RSHFT: u16 = 0x36;
+// sent with KEY_E0 by boards that have a dedicated Assistant key
+pub const K_ASSISTANT: u16 = 0x58;
+
+pub const K_BACKSP: u16 = 0x0E;
+pub const K_DELETE: u16 = 0x53;
+pub const K_LOCK: u16 = 0x5D;
+
+pub const K_UP: u16 = 0x48;
+pub const K_DOWN: u16 = 0x50;
+pub const K_LEFT: u16 = 0x4B;
+pub const K_RIGHT: u16 = 0x4D;
+
+pub const K_PGUP: u16 = 0x49;
+pub const K_HOME: u16 = 0x47;
+pub const K_END: u16 = 0x4F;
+pub const K_PGDN: u16 = 0x51;
+
+// Vivaldi Keycodes
+pub const VIVALDI_BACK: u16 = 0x6A;
+pub const VIVALDI_FWD: u16 = 0x69;
+pub const VIVALDI_REFRESH: u16 = 0x67;
+pub const VIVALDI_FULLSCREEN: u16 = 0x11;
+pub const VIVALDI_OVERVIEW: u16 = 0x12;
+pub const VIVALDI_SNAPSHOT: u16 = 0x13;
+pub const VIVALDI_BRIGHTNESS_DN: u16 = 0x14;
+pub const VIVALDI_BRIGHTNESS_UP: u16 = 0x15;
+pub const VIVALDI_PRIVACY_TOGGLE: u16 = 0x16;
+pub const VIVALDI_KBD_BKLIGHT_DOWN: u16 = 0x17;
+pub const VIVALDI_KBD_BKLIGHT_UP: u16 = 0x18;
+pub const VIVALDI_KBD_BKLIGHT_TOGGLE: u16 = 0x1E;
+pub const VIVALDI_PLAY_PAUSE: u16 = 0x1A;
+pub const VIVALDI_MUTE: u16 = 0x20;
+pub const VIVALDI_VOL_DN: u16 = 0x2E;
+pub const VIVALDI_VOL_UP: u16 = 0x30;
+pub const VIVALDI_NEXT_TRACK: u16 = 0x19;
+pub const VIVALDI_PREV_TRACK: u16 = 0x10;
+pub const VIVALDI_MIC_MUTE: u16 = 0x1B;
+
+pub const KEY_BREAK: u16 = 1;
+pub const KEY_E0: u16 = 2;
+pub const KEY_E1: u16 = 4;
+
+pub const FUNCTION_KEYS: [u16; 16] = [
+    0x3B, 0x3C, 0x3D, 0x3E, 0x3F, 0x40, 0x41, 0x42, 0x43, 0x44, 0x57, 0x58,
+    0x64, 0x65, 0x66, 0x67, // F13-F16
+];
+
+pub fn format_flags(flags: u16) -> String {
+    let mut flag_strs = Vec::new();
+
+    if flags & KEY_BREAK != 0 { flag_strs.push("BREAK"); }
+    if flags & KEY_E0 != 0 { flag_strs.push("E0"); }
+    if flags & KEY_E1 != 0 { flag_strs.push("E1"); }
+
+    if flag_strs.is_empty() {
+        "NONE".to_string()
+    } else {
+        flag_strs.join("|")
+    }
+}
+
+// key names
+//
+// Primary names for every key in scan code set 1 plus the Chromebook-specific
+// keys above. `(name, make_code, flags)`; the first entry for a code is the
+// name the decoder prints. Flags here only ever hold KEY_E0/KEY_E1.
+const KEY_NAMES: &[(&str, u16, u16)] = &[
+    ("Esc", 0x01, 0),
+    ("1", 0x02, 0),
+    ("2", 0x03, 0),
+    ("3", 0x04, 0),
+    ("4", 0x05, 0),
+    ("5", 0x06, 0),
+    ("6", 0x07, 0),
+    ("7", 0x08, 0),
+    ("8", 0x09, 0),
+    ("9", 0x0A, 0),
+    ("0", 0x0B, 0),
+    ("Minus", 0x0C, 0),
+    ("Equal", 0x0D, 0),
+    ("Backspace", K_BACKSP, 0),
+    ("Tab", 0x0F, 0),
+    ("Q", 0x10, 0),
+    ("W", 0x11, 0),
+    ("E", 0x12, 0),
+    ("R", 0x13, 0),
+    ("T", 0x14, 0),
+    ("Y", 0x15, 0),
+    ("U", 0x16, 0),
+    ("I", 0x17, 0),
+    ("O", 0x18, 0),
+    ("P", 0x19, 0),
+    ("LeftBracket", 0x1A, 0),
+    ("RightBracket", 0x1B, 0),
+    ("Enter", 0x1C, 0),
+    ("LCtrl", K_LCTRL, 0),
+    ("A", 0x1E, 0),
+    ("S", 0x1F, 0),
+    ("D", 0x20, 0),
+    ("F", 0x21, 0),
+    ("G", 0x22, 0),
+    ("H", 0x23, 0),
+    ("J", 0x24, 0),
+    ("K", 0x25, 0),
+    ("L", 0x26, 0),
+    ("Semicolon", 0x27, 0),
+    ("Apostrophe", 0x28, 0),
+    ("Grave", 0x29, 0),
+    ("LShift", K_LSHFT, 0),
+    ("Backslash", 0x2B, 0),
+    ("Z", 0x2C, 0),
+    ("X", 0x2D, 0),
+    ("C", 0x2E, 0),
+    ("V", 0x2F, 0),
+    ("B", 0x30, 0),
+    ("N", 0x31, 0),
+    ("M", 0x32, 0),
+    ("Comma", 0x33, 0),
+    ("Period", 0x34, 0),
+    ("Slash", 0x35, 0),
+    ("RShift", K_RSHFT, 0),
+    ("KpMultiply", 0x37, 0),
+    ("LAlt", K_LALT, 0),
+    ("Space", 0x39, 0),
+    ("CapsLock", 0x3A, 0),
+    ("F1", FUNCTION_KEYS[0], 0),
+    ("F2", FUNCTION_KEYS[1], 0),
+    ("F3", FUNCTION_KEYS[2], 0),
+    ("F4", FUNCTION_KEYS[3], 0),
+    ("F5", FUNCTION_KEYS[4], 0),
+    ("F6", FUNCTION_KEYS[5], 0),
+    ("F7", FUNCTION_KEYS[6], 0),
+    ("F8", FUNCTION_KEYS[7], 0),
+    ("F9", FUNCTION_KEYS[8], 0),
+    ("F10", FUNCTION_KEYS[9], 0),
+    ("F11", FUNCTION_KEYS[10], 0),
+    ("F12", FUNCTION_KEYS[11], 0),
+    ("F13", FUNCTION_KEYS[12], 0),
+    ("F14", FUNCTION_KEYS[13], 0),
+    ("F15", FUNCTION_KEYS[14], 0),
+    ("F16", FUNCTION_KEYS[15], 0),
+    ("NumLock", 0x45, 0),
+    ("ScrollLock", 0x46, 0),
+    ("Kp7", 0x47, 0),
+    ("Kp8", 0x48, 0),
+    ("Kp9", 0x49, 0),
+    ("KpMinus", 0x4A, 0),
+    ("Kp4", 0x4B, 0),
+    ("Kp5", 0x4C, 0),
+    ("Kp6", 0x4D, 0),
+    ("KpPlus", 0x4E, 0),
+    ("Kp1", 0x4F, 0),
+    ("Kp2", 0x50, 0),
+    ("Kp3", 0x51, 0),
+    ("Kp0", 0x52, 0),
+    ("KpDecimal", 0x53, 0),
+    ("IntlBackslash", 0x56, 0),
+    ("Lock", K_LOCK, 0),
+    // extended keys
+    ("KpEnter", 0x1C, KEY_E0),
+    ("RCtrl", K_LCTRL, KEY_E0),
+    ("KpDivide", 0x35, KEY_E0),
+    ("PrintScreen", 0x37, KEY_E0),
+    ("RAlt", K_LALT, KEY_E0),
+    ("Home", K_HOME, KEY_E0),
+    ("Up", K_UP, KEY_E0),
+    ("PageUp", K_PGUP, KEY_E0),
+    ("Left", K_LEFT, KEY_E0),
+    ("Right", K_RIGHT, KEY_E0),
+    ("End", K_END, KEY_E0),
+    ("Down", K_DOWN, KEY_E0),
+    ("PageDown", K_PGDN, KEY_E0),
+    ("Insert", 0x52, KEY_E0),
+    ("Delete", K_DELETE, KEY_E0),
+    ("LWin", K_LWIN, KEY_E0),
+    ("RWin", 0x5C, KEY_E0),
+    ("Menu", 0x5D, KEY_E0),
+    ("Assistant", K_ASSISTANT, KEY_E0),
+    ("Pause", K_LCTRL, KEY_E1),
+    // Vivaldi top row
+    ("Vivaldi.Back", VIVALDI_BACK, KEY_E0),
+    ("Vivaldi.Forward", VIVALDI_FWD, KEY_E0),
+    ("Vivaldi.Refresh", VIVALDI_REFRESH, KEY_E0),
+    ("Vivaldi.Fullscreen", VIVALDI_FULLSCREEN, KEY_E0),
+    ("Vivaldi.Overview", VIVALDI_OVERVIEW, KEY_E0),
+    ("Vivaldi.Snapshot", VIVALDI_SNAPSHOT, KEY_E0),
+    ("Vivaldi.BrightnessDown", VIVALDI_BRIGHTNESS_DN, KEY_E0),
+    ("Vivaldi.BrightnessUp", VIVALDI_BRIGHTNESS_UP, KEY_E0),
+    ("Vivaldi.PrivacyToggle", VIVALDI_PRIVACY_TOGGLE, KEY_E0),
+    ("Vivaldi.KbdBacklightDown", VIVALDI_KBD_BKLIGHT_DOWN, KEY_E0),
+    ("Vivaldi.KbdBacklightUp", VIVALDI_KBD_BKLIGHT_UP, KEY_E0),
+    ("Vivaldi.KbdBacklightToggle", VIVALDI_KBD_BKLIGHT_TOGGLE, KEY_E0),
+    ("Vivaldi.PlayPause", VIVALDI_PLAY_PAUSE, KEY_E0),
+    ("Vivaldi.Mute", VIVALDI_MUTE, KEY_E0),
+    ("Vivaldi.VolumeDown", VIVALDI_VOL_DN, KEY_E0),
+    ("Vivaldi.VolumeUp", VIVALDI_VOL_UP, KEY_E0),
+    ("Vivaldi.NextTrack", VIVALDI_NEXT_TRACK, KEY_E0),
+    ("Vivaldi.PrevTrack", VIVALDI_PREV_TRACK, KEY_E0),
+    ("Vivaldi.MicMute", VIVALDI_MIC_MUTE, KEY_E0),
+];
+
+// extra spellings accepted when parsing, never printed
+const KEY_ALIASES: &[(&str, &str)] = &[
+    ("Escape", "Esc"),
+    ("Backsp", "Backspace"),
+    ("Return", "Enter"),
+    ("Ctrl", "LCtrl"),
+    ("Alt", "LAlt"),
+    ("Shift", "LShift"),
+    ("Win", "LWin"),
+    ("Search", "LWin"),
+    ("Del", "Delete"),
+    ("Ins", "Insert"),
+    ("PgUp", "PageUp"),
+    ("PgDn", "PageDown"),
+    ("PageDn", "PageDown"),
+];
+
+const FLAG_MASK: u16 = KEY_BREAK | KEY_E0 | KEY_E1;
+
+/// Returns the table name of a key, ignoring the break flag.
+pub fn key_name(make_code: u16, flags: u16) -> Option<&'static str> {
+    let prefix = flags & (KEY_E0 | KEY_E1);
+    KEY_NAMES
+        .iter()
+        .find(|&&(_, code, key_flags)| code == make_code && key_flags == prefix)
+        .map(|&(name, _, _)| name)
+}
+
+/// Looks up a key name or alias, case-insensitively. Top-row keys may drop
+/// their `Vivaldi.` prefix when the short name is not taken (`Overview`).
+pub fn lookup_key(name: &str) -> Option<RemapCfgKey> {
+    let name = KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map_or(name, |&(_, target)| target);
+    let find = |name: &str| {
+        KEY_NAMES
+            .iter()
+            .find(|(key_name, _, _)| key_name.eq_ignore_ascii_case(name))
+            .map(|&(_, make_code, flags)| RemapCfgKey::with_values(make_code, flags))
+    };
+    find(name).or_else(|| find(&format!("Vivaldi.{}", name)))
+}
+
+/// Formats a key as `[E0 |E1 ]<name or 0xNN>[ Break]`, the inverse of `parse_key`.
+/// Keys carrying flag bits outside KEY_BREAK/KEY_E0/KEY_E1 have no such form.
+pub fn format_key(key: RemapCfgKey) -> Option<String> {
+    let RemapCfgKey { make_code, flags } = key;
+    if flags & !FLAG_MASK != 0 {
+        return None;
+    }
+
+    let mut text = match key_name(make_code, flags) {
+        Some(name) => name.to_string(),
+        None => {
+            let mut text = String::new();
+            if flags & KEY_E0 != 0 { text.push_str("E0 "); }
+            if flags & KEY_E1 != 0 { text.push_str("E1 "); }
+            text.push_str(&format!("0x{:02X}", make_code));
+            text
+        }
+    };
+    if flags & KEY_BREAK != 0 {
+        text.push_str(" Break");
+    }
+    Some(text)
+}
+
+/// Parses a key written as a name (`Vivaldi.Back`, `LCtrl Break`) or as a raw
+/// make code with optional prefixes (`E0 0x53`). Names carry their own E0/E1
+/// flag; explicit `E0`, `E1` and `Break` words are added on top.
+pub fn parse_key(text: &str) -> Result<RemapCfgKey, Error> {
+    let invalid = || Error::InvalidKey { text: text.to_string() };
+
+    let mut flags = 0;
+    let mut code = None;
+    for word in text.split_whitespace() {
+        if word.eq_ignore_ascii_case("E0") {
+            flags |= KEY_E0;
+        } else if word.eq_ignore_ascii_case("E1") {
+            flags |= KEY_E1;
+        } else if word.eq_ignore_ascii_case("Break") {
+            flags |= KEY_BREAK;
+        } else if code.is_none() {
+            code = Some(word);
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    let code = code.ok_or_else(invalid)?;
+    let key = match lookup_key(code) {
+        Some(key) => key,
+        None => {
+            let hex = code
+                .strip_prefix("0x")
+                .or_else(|| code.strip_prefix("0X"))
+                .ok_or_else(invalid)?;
+            RemapCfgKey::with_values(u16::from_str_radix(hex, 16).map_err(|_| invalid())?, 0)
+        }
+    };
+    Ok(RemapCfgKey::with_values(key.make_code, key.flags | flags))
+}
+
+impl fmt::Display for RemapCfgKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match format_key(*self) {
+            Some(text) => f.write_str(&text),
+            None => write!(f, "0x{:02X} (flags: 0x{:04X})", { self.make_code }, { self.flags }),
+        }
+    }
+}
+
+impl FromStr for RemapCfgKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_key(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_carry_their_prefix() {
+        assert_eq!(parse_key("Delete").unwrap(), RemapCfgKey::with_values(K_DELETE, KEY_E0));
+        assert_eq!(parse_key("vivaldi.back").unwrap(), RemapCfgKey::with_values(VIVALDI_BACK, KEY_E0));
+        assert_eq!(parse_key("Overview").unwrap(), RemapCfgKey::with_values(VIVALDI_OVERVIEW, KEY_E0));
+        assert_eq!(parse_key("LCtrl Break").unwrap(), RemapCfgKey::with_values(K_LCTRL, KEY_BREAK));
+    }
+
+    #[test]
+    fn raw_codes_take_explicit_prefixes() {
+        assert_eq!(parse_key("0x1F").unwrap(), RemapCfgKey::with_values(0x1F, 0));
+        assert_eq!(parse_key("E0 0x53").unwrap(), RemapCfgKey::with_values(0x53, KEY_E0));
+        assert_eq!(parse_key("E1 0x1D Break").unwrap(), RemapCfgKey::with_values(0x1D, KEY_E1 | KEY_BREAK));
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        for text in ["", "Nowhere", "0xZZ", "A B", "E0"] {
+            assert!(matches!(parse_key(text), Err(Error::InvalidKey { .. })), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn format_is_the_inverse_of_parse() {
+        for key in [
+            RemapCfgKey::with_values(K_DELETE, KEY_E0),
+            RemapCfgKey::with_values(K_LWIN, KEY_E0 | KEY_BREAK),
+            RemapCfgKey::with_values(0x7E, 0),
+            RemapCfgKey::with_values(0x7E, KEY_E1),
+        ] {
+            let text = format_key(key).unwrap();
+            assert_eq!(parse_key(&text).unwrap(), key, "{}", text);
+        }
+        assert_eq!(format_key(RemapCfgKey::with_values(0x53, KEY_E0)).as_deref(), Some("Delete"));
+        assert_eq!(format_key(RemapCfgKey::with_values(0x1D, KEY_E0 | KEY_BREAK)).as_deref(), Some("RCtrl Break"));
+    }
+
+    #[test]
+    fn unknown_flag_bits_have_no_name() {
+        assert_eq!(format_key(RemapCfgKey::with_values(0x1D, 0x0100)), None);
+    }
+}
@@ -0,0 +1,34 @@
+//! Reader and writer for the CrosKeyboard4 `croskbsettings.bin` remap file.
+//!
+//! [`Config`] is the owned model of a settings file; it converts to and from
+//! the driver's binary layout and the JSON format used by the command-line tool.
+
+#![forbid(unsafe_code)]
+
+pub mod boards;
+pub mod config;
+pub mod demo;
+pub mod diff;
+pub mod dsl;
+pub mod equiv;
+pub mod error;
+pub mod explain;
+pub mod install;
+pub mod json;
+pub mod keys;
+pub mod lint;
+pub mod presets;
+pub mod repair;
+pub mod simulator;
+pub mod sysfs;
+pub mod table;
+#[cfg(test)]
+mod testing;
+
+pub use boards::{board, Board};
+pub use config::{
+    AnyModifier, Config, Header, KeyState, Modifier, ModifierSet, RemapCfgKey, RemapOverride, Rule,
+};
+pub use demo::{default_config, demo_config};
+pub use error::{Error, Result};
+pub use presets::{preset, Preset, PRESETS};
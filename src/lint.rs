@@ -0,0 +1,252 @@
+//! Static checks for conflicting, shadowed and unreachable entries.
+//!
+//! The driver applies the first entry whose original key and modifier
+//! conditions match, so for every original key the checks enumerate all 256
+//! modifier combinations and see which entry each one lands on.
+
+use std::fmt;
+
+use crate::config::{Config, KeyState, Modifier, ModifierSet, RemapOverride};
+use crate::dsl::format_rule;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// Two entries for the same key match some common modifier states;
+    /// the earlier one wins for those states.
+    Overlap,
+    /// Every state the entry matches is taken by earlier entries.
+    Shadowed,
+    /// The entry cannot match any key event.
+    NeverFires,
+    /// The entry matches but leaves the key unchanged.
+    NoEffect,
+    /// The entry releases a held Search key before pressing another key,
+    /// which opens the Start menu.
+    SearchTap,
+}
+
+impl LintKind {
+    /// Shadowed and never-firing entries are dead weight and almost always
+    /// a mistake; overlaps, no-op entries and Search taps can be intentional.
+    pub fn is_error(self) -> bool {
+        matches!(self, LintKind::Shadowed | LintKind::NeverFires)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    /// Index of the entry the issue is about.
+    pub entry: usize,
+    /// Earlier entries involved in an overlap or shadowing.
+    pub others: Vec<usize>,
+    /// A modifier state that demonstrates the issue, if there is one.
+    pub example: Option<ModifierSet>,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = if self.kind.is_error() { "error" } else { "warning" };
+        write!(f, "{}: config {}: {}", level, self.entry, self.message)
+    }
+}
+
+/// Modifier states the driver can observe with this header.
+pub(crate) fn possible_states(config: &Config) -> Vec<ModifierSet> {
+    ModifierSet::all()
+        .filter(|held| {
+            config.header.has_assistant_key != RemapOverride::Disable
+                || !held.contains(Modifier::Assistant)
+        })
+        .collect()
+}
+
+pub fn lint(config: &Config) -> Vec<LintIssue> {
+    let states = possible_states(config);
+    let mut issues = Vec::new();
+
+    for (j, rule) in config.entries.iter().enumerate() {
+        if rule.original_key.is_empty() {
+            issues.push(LintIssue {
+                kind: LintKind::NeverFires,
+                entry: j,
+                others: Vec::new(),
+                example: None,
+                message: "original key is empty".to_string(),
+            });
+            continue;
+        }
+
+        let matched: Vec<ModifierSet> = states
+            .iter()
+            .copied()
+            .filter(|&held| rule.matches_modifiers(held))
+            .collect();
+        if matched.is_empty() {
+            let message = if rule.assistant == KeyState::Enforce {
+                format!("`{}` requires Assistant, but has_assistant_key is Disable", format_rule(rule))
+            } else {
+                format!("`{}` has no satisfiable modifier state", format_rule(rule))
+            };
+            issues.push(LintIssue {
+                kind: LintKind::NeverFires,
+                entry: j,
+                others: Vec::new(),
+                example: None,
+                message,
+            });
+            continue;
+        }
+
+        // earlier entries for the same key, and which of our states each takes
+        let earlier: Vec<(usize, Vec<ModifierSet>)> = config.entries[..j]
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| other.matches_key(rule.original_key))
+            .map(|(i, other)| {
+                let common = matched.iter().copied().filter(|&held| other.matches_modifiers(held)).collect();
+                (i, common)
+            })
+            .filter(|(_, common): &(usize, Vec<ModifierSet>)| !common.is_empty())
+            .collect();
+
+        let live = matched.iter().any(|held| earlier.iter().all(|(_, common)| !common.contains(held)));
+
+        if !live {
+            let others: Vec<usize> = earlier.iter().map(|&(i, _)| i).collect();
+            issues.push(LintIssue {
+                kind: LintKind::Shadowed,
+                entry: j,
+                example: Some(matched[0]),
+                message: format!(
+                    "`{}` is unreachable: {} every modifier state it does",
+                    format_rule(rule),
+                    if others.len() == 1 {
+                        format!("config {} matches", others[0])
+                    } else {
+                        format!("configs {} match", join_indices(&others))
+                    }
+                ),
+                others,
+            });
+            continue;
+        }
+
+        for (i, common) in &earlier {
+            issues.push(LintIssue {
+                kind: LintKind::Overlap,
+                entry: j,
+                others: vec![*i],
+                example: Some(common[0]),
+                message: format!(
+                    "`{}` overlaps config {} (`{}`) in {} modifier state{}, e.g. {}; config {} wins",
+                    format_rule(rule),
+                    i,
+                    format_rule(&config.entries[*i]),
+                    common.len(),
+                    if common.len() == 1 { "" } else { "s" },
+                    common[0],
+                    i
+                ),
+            });
+        }
+
+        let unchanged = rule.remapped_key.is_empty() || rule.matches_key(rule.remapped_key);
+        if !rule.remap_vivaldi_to_fn_keys && unchanged && rule.additional_keys().next().is_none() {
+            issues.push(LintIssue {
+                kind: LintKind::NoEffect,
+                entry: j,
+                others: Vec::new(),
+                example: None,
+                message: format!("`{}` passes the key through unchanged", format_rule(rule)),
+            });
+        }
+
+        if rule.releases_search_first() {
+            issues.push(LintIssue {
+                kind: LintKind::SearchTap,
+                entry: j,
+                others: Vec::new(),
+                example: None,
+                message: format!(
+                    "`{}` releases Search before pressing another key; Windows opens the Start menu",
+                    format_rule(rule)
+                ),
+            });
+        }
+    }
+
+    issues
+}
+
+fn join_indices(indices: &[usize]) -> String {
+    indices.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::compile;
+
+    fn kinds(text: &str) -> Vec<(LintKind, usize, Vec<usize>)> {
+        lint(&compile(text).unwrap()).into_iter().map(|issue| (issue.kind, issue.entry, issue.others)).collect()
+    }
+
+    #[test]
+    fn default_config_has_no_errors() {
+        let issues = lint(&crate::default_config());
+        assert!(issues.iter().all(|issue| !issue.kind.is_error()), "{:?}", issues);
+    }
+
+    #[test]
+    fn later_entry_covered_by_earlier_ones_is_shadowed() {
+        let text = "Ctrl + Left => Home\n!Ctrl + Left => End\nShift + Left => PageUp\n";
+        assert_eq!(kinds(text), [(LintKind::Shadowed, 2, vec![0, 1])]);
+    }
+
+    #[test]
+    fn partial_overlap_is_a_warning() {
+        let text = "Ctrl + Left => Home\nShift + Left => End\n";
+        let issues = lint(&compile(text).unwrap());
+        assert_eq!(issues.len(), 1);
+        let issue = &issues[0];
+        assert_eq!((issue.kind, issue.entry, issue.others.as_slice()), (LintKind::Overlap, 1, &[0][..]));
+        assert!(!issue.kind.is_error());
+        let example = issue.example.unwrap();
+        assert!(example.contains(Modifier::LeftCtrl) && example.contains(Modifier::LeftShift));
+        assert!(issue.message.contains("in 64 modifier states"), "{}", issue.message);
+    }
+
+    #[test]
+    fn assistant_entry_never_fires_without_an_assistant_key() {
+        let text = "set has_assistant_key = Disable\nAssistant + Left => Home\n";
+        let issues = lint(&compile(text).unwrap());
+        assert_eq!(issues[0].kind, LintKind::NeverFires);
+        assert!(issues[0].message.contains("has_assistant_key is Disable"));
+        assert!(kinds("Assistant + Left => Home").is_empty());
+    }
+
+    #[test]
+    fn empty_original_key_never_fires() {
+        let mut config = compile("Ctrl + Left => Home").unwrap();
+        config.entries[0].original_key = Default::default();
+        assert_eq!(lint(&config)[0].kind, LintKind::NeverFires);
+    }
+
+    #[test]
+    fn entry_sending_the_key_unchanged_has_no_effect() {
+        assert_eq!(kinds("Ctrl + Left => Left"), [(LintKind::NoEffect, 0, vec![])]);
+        assert_eq!(kinds("Ctrl + Left => None"), [(LintKind::NoEffect, 0, vec![])]);
+        assert!(kinds("Ctrl + Left => release LCtrl + Left").is_empty());
+    }
+
+    #[test]
+    fn releasing_search_before_any_press_is_a_warning() {
+        let text = "Search + Left => release modifiers + Home\n\
+                    Search + C => release modifiers + LCtrl + C\n\
+                    !Search + Lock => release LWin + L\n";
+        assert_eq!(kinds(text), [(LintKind::SearchTap, 0, vec![])]);
+        assert!(!LintKind::SearchTap.is_error());
+    }
+}
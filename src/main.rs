@@ -1,807 +1,737 @@
 use std::fs::{self, File};
-use std::io::Write;
-use std::mem;
-use std::path::Path;
-use std::io;
-
-use serde::{Serialize, Deserialize};
-use serde_json;
-
-// config file location
-const CONFIG_PATH: &str = "C:\\Windows\\System32\\drivers\\croskbsettings.bin";
-
-// Keycodes and constants
-const K_LCTRL: u16 = 0x1D;
-const K_LALT: u16 = 0x38;
-const K_LSHFT: u16 = 0x2A;
-const K_LWIN: u16 = 0x5B;
-const K_RSHFT: u16 = 0x36;
-
-const K_BACKSP: u16 = 0x0E;
-const K_DELETE: u16 = 0x53;
-const K_LOCK: u16 = 0x5D;
-
-const K_UP: u16 = 0x48;
-const K_DOWN: u16 = 0x50;
-const K_LEFT: u16 = 0x4B;
-const K_RIGHT: u16 = 0x4D;
-
-const K_PGUP: u16 = 0x49;
-const K_HOME: u16 = 0x47;
-const K_END: u16 = 0x4F;
-const K_PGDN: u16 = 0x51;
-
-// Vivaldi Keycodes
-const VIVALDI_BACK: u16 = 0x6A;
-const VIVALDI_FWD: u16 = 0x69;
-const VIVALDI_REFRESH: u16 = 0x67;
-const VIVALDI_FULLSCREEN: u16 = 0x11;
-const VIVALDI_OVERVIEW: u16 = 0x12;
-const VIVALDI_SNAPSHOT: u16 = 0x13;
-const VIVALDI_BRIGHTNESS_DN: u16 = 0x14;
-const VIVALDI_BRIGHTNESS_UP: u16 = 0x15;
-const VIVALDI_PRIVACY_TOGGLE: u16 = 0x16;
-const VIVALDI_KBD_BKLIGHT_DOWN: u16 = 0x17;
-const VIVALDI_KBD_BKLIGHT_UP: u16 = 0x18;
-const VIVALDI_KBD_BKLIGHT_TOGGLE: u16 = 0x1E;
-const VIVALDI_PLAY_PAUSE: u16 = 0x1A;
-const VIVALDI_MUTE: u16 = 0x20;
-const VIVALDI_VOL_DN: u16 = 0x2E;
-const VIVALDI_VOL_UP: u16 = 0x30;
-const VIVALDI_NEXT_TRACK: u16 = 0x19;
-const VIVALDI_PREV_TRACK: u16 = 0x10;
-const VIVALDI_MIC_MUTE: u16 = 0x1B;
-
-const KEY_BREAK: u16 = 1;
-const KEY_E0: u16 = 2;
-
-// C++ multi-char literal 'CrKB' on little-endian systems stores bytes as: 42 4B 72 43
-// Which reads as "BKrC" in ASCII. We need to match this exact byte sequence.
-const CFG_MAGIC: u32 = u32::from_le_bytes(*b"BKrC");
-
-const FUNCTION_KEYS: [u16; 16] = [
-    0x3B, 0x3C, 0x3D, 0x3E, 0x3F, 0x40, 0x41, 0x42, 0x43, 0x44, 0x57, 0x58,
-    0x64, 0x65, 0x66, 0x67, // F13-F16
-];
-
-// enums on c and rust are not the same, hence this structure
-type RemapCfgKeyState = i32;
-const KEY_STATE_NO_DETECT: i32 = 0;
-const KEY_STATE_ENFORCE: i32 = 1;
-const KEY_STATE_ENFORCE_NOT: i32 = 2;
-
-type RemapCfgOverride = i32;
-const REMAP_AUTO_DETECT: i32 = 0;
-const REMAP_ENABLE: i32 = 1;
-const REMAP_DISABLE: i32 = 2;
-
-// binary structures
-#[repr(C, packed(1))]
-#[derive(Debug, Copy, Clone)]
-struct RemapCfgKey {
-    make_code: u16,
-    flags: u16,
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+
+use config_generator::config::{KeyState, CFG_MAGIC, CFG_SIZE, HEADER_SIZE};
+use config_generator::equiv::check_equivalence;
+use config_generator::repair::{repair, RepairOptions};
+use config_generator::install::{find_windows_roots, Installer, DEFAULT_KEEP_BACKUPS, MOUNT_BASES};
+use config_generator::explain::{explain, explain_with_top_row};
+use config_generator::keys::{format_flags, format_key, parse_key};
+use config_generator::simulator::{format_event, is_break, parse_events, Simulator};
+use config_generator::sysfs::{detect_board, DetectedBoard, DEFAULT_SYSFS_ROOT};
+use config_generator::{boards, demo_config, diff, presets, dsl, lint, table, Config, ModifierSet, RemapCfgKey};
+
+const USAGE: &str = "\
+Usage:
+  config-generator decode <input.bin|-> [-o <output.json|->]
+  config-generator encode <input.json|-> [-o <output.bin|->]
+  config-generator demo [-o <output.bin|->]
+  config-generator preset [<name>] [--board <board>|auto] [--sysfs <dir>] [-o <output.bin|->]
+  config-generator boards
+  config-generator compile <rules.txt|-> [-o <output.bin|->]
+  config-generator decompile <input.bin|-> [-o <rules.txt|->]
+  config-generator lint <config>
+  config-generator simulate <config> [events] [--top-row <keys> | --board <board>|auto]
+  config-generator explain <config> <key> [modifiers] [--top-row <keys> | --board <board>|auto]
+  config-generator install <config> [--root <dir>] [--keep <n>] [--yes]
+  config-generator rollback [--root <dir>]
+  config-generator detect
+  config-generator repair <input.bin|-> [-o <output.bin|->] [--report <report.json>] [--keep-suspect]
+  config-generator diff <old> <new> [--format text|json]
+  config-generator equiv <left-config> <right-config> [--limit <n>]
+  config-generator table <config> [--format csv|json] [--top-row <keys> | --board <board>|auto] [-o <output|->]
+
+preset without a name lists the built-in layouts; demo is the 'default' preset.
+With --board, the preset's Fn entries cover exactly that board's top row and
+has_assistant_key is set for it (a name defaults to 'default'). boards lists the
+known boards and their top rows. --board auto reads the top row and DMI product
+name of the running machine from /sys, or from the tree given with --sysfs.
+
+A <config> may be a binary settings file, JSON or rule text; the format is detected.
+
+simulate feeds key events such as '+LCtrl, +Left, -Left, -LCtrl' (read from
+stdin when not given) through the driver model and prints what it sends on.
+explain shows which entry fires for <key> with [modifiers] held, e.g.
+'Vivaldi.Fullscreen LCtrl+LShift', what it sends and which later entries it shadows.
+table expands a config into every original key under all 256 modifier
+combinations, as CSV (the default) or JSON.
+install validates a config and writes it to Windows\\System32\\drivers\\croskbsettings.bin
+under --root (C:\\ on Windows), keeping the last --keep (default 5) versions as
+backups; rollback restores the newest backup. Outside Windows, a Windows partition
+mounted under /mnt, /media or /run/media is used when --root is not given, and
+the config being replaced is shown before it is overwritten (confirm or pass --yes).
+detect lists mounted Windows partitions and the config installed on each.
+repair recovers the complete entries of a damaged file, drops entries that look
+like garbage (unless --keep-suspect) and rewrites the entry count.
+diff compares two configs entry by entry and prints added, removed, moved and
+changed entries; it exits with 1 if they differ.
+equiv checks that two configs behave the same for every key and modifier
+state, printing up to --limit (default 20) counterexamples; it exits with 1 if not.
+--top-row lists the board's top-row keys, left to right, separated by commas;
+--board takes them from a known board instead, and --board auto (or --sysfs <dir>)
+detects them.
+
+A path of '-' reads from stdin or writes to stdout. Without -o, output goes to stdout;
+only the commands shown with -o accept it.";
+
+enum Command {
+    Decode { input: String, output: String },
+    Encode { input: String, output: String },
+    Demo { output: String },
+    Preset { name: Option<String>, board: Option<TopRow>, output: String },
+    Boards,
+    Compile { input: String, output: String },
+    Decompile { input: String, output: String },
+    Lint { input: String },
+    Simulate { input: String, events: Option<String>, top_row: Option<TopRow> },
+    Explain { input: String, key: String, modifiers: String, top_row: Option<TopRow> },
+    Table { input: String, output: String, format: String, top_row: Option<TopRow> },
+    Equiv { left: String, right: String, limit: usize },
+    Diff { old: String, new: String, format: String },
+    Repair { input: String, output: String, report: Option<String>, keep_suspect: bool },
+    Install { input: String, root: Option<String>, keep: usize, yes: bool },
+    Rollback { root: Option<String> },
+    Detect,
+    Help,
 }
 
-impl RemapCfgKey {
-    const fn new() -> Self {
-        Self {
-            make_code: 0,
-            flags: 0,
-        }
-    }
+// options that take no value
+const SWITCHES: &[&str] = &["--keep-suspect", "--yes"];
 
-    const fn with_values(make_code: u16, flags: u16) -> Self {
-        Self { make_code, flags }
-    }
+struct Args {
+    positional: Vec<String>,
+    output: Option<String>,
+    options: Vec<(String, Option<String>)>,
 }
 
-#[repr(C, packed(1))]
-#[derive(Copy, Clone)]
-struct RemapCfg {
-    left_ctrl: i32,
-    left_alt: i32,
-    search: i32,
-    assistant: i32,
-    left_shift: i32,
-    right_ctrl: i32,
-    right_alt: i32,
-    right_shift: i32,
-    original_key: RemapCfgKey,
-    remap_vivaldi_to_fn_keys: u8,
-    remapped_key: RemapCfgKey,
-    additional_keys: [RemapCfgKey; 8],
-}
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let mut parsed = Args { positional: Vec::new(), output: None, options: Vec::new() };
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    parsed.output = Some(rest.next().ok_or(format!("{} requires a path", arg))?.clone());
+                }
+                "-" => parsed.positional.push(arg.clone()),
+                _ if arg.starts_with("--") => {
+                    let (name, value) = match arg.split_once('=') {
+                        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                        None if SWITCHES.contains(&arg.as_str()) => (arg.clone(), None),
+                        None => (arg.clone(), Some(rest.next().ok_or(format!("{} requires a value", arg))?.clone())),
+                    };
+                    parsed.options.push((name, value));
+                }
+                _ => parsed.positional.push(arg.clone()),
+            }
+        }
+        Ok(parsed)
+    }
 
-impl RemapCfg {
-    fn new() -> Self {
-        Self {
-            left_ctrl: KEY_STATE_NO_DETECT,
-            left_alt: KEY_STATE_NO_DETECT,
-            search: KEY_STATE_NO_DETECT,
-            assistant: KEY_STATE_NO_DETECT,
-            left_shift: KEY_STATE_NO_DETECT,
-            right_ctrl: KEY_STATE_NO_DETECT,
-            right_alt: KEY_STATE_NO_DETECT,
-            right_shift: KEY_STATE_NO_DETECT,
-            original_key: RemapCfgKey::new(),
-            remap_vivaldi_to_fn_keys: 0,
-            remapped_key: RemapCfgKey::new(),
-            additional_keys: [RemapCfgKey::new(); 8],
+    // takes the positional arguments, requiring between `min` and `names.len()`
+    fn positionals(&mut self, subcommand: &str, min: usize, names: &[&str]) -> Result<Vec<String>, String> {
+        if self.positional.len() < min {
+            return Err(format!("{} requires {}", subcommand, names[self.positional.len()]));
+        }
+        if self.positional.len() > names.len() {
+            return Err(format!("unexpected argument '{}'", self.positional[names.len()]));
         }
+        Ok(std::mem::take(&mut self.positional))
     }
-}
 
-#[repr(C, packed(1))]
-struct RemapCfgsHeader {
-    magic: u32,
-    remappings: u32,
-    flip_search_and_assistant_on_pixelbook: u8,
-    has_assistant_key: i32,
-    is_non_chrome_ec: i32,
-}
+    fn input(&mut self, subcommand: &str) -> Result<String, String> {
+        Ok(self.positionals(subcommand, 1, &["an input file"])?.remove(0))
+    }
 
-// json structures
+    fn switch(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct RemapCfgKeyJson {
-    make_code: u16,
-    #[serde(skip_serializing_if = "String::is_empty", default)]
-    make_code_hex: String,
-    flags: u16,
-    #[serde(skip_serializing_if = "Vec::is_empty", default)]
-    flags_decoded: Vec<String>,
-}
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(n, _)| n == name).and_then(|(_, v)| v.as_deref())
+    }
 
-impl RemapCfgKeyJson {
-    fn new(make_code: u16, flags: u16) -> Self {
-        let mut flags_decoded = Vec::new();
-        if flags & 0x0001 != 0 { flags_decoded.push("KEY_BREAK".to_string()); }
-        if flags & 0x0002 != 0 { flags_decoded.push("KEY_E0".to_string()); }
-        if flags & 0x0004 != 0 { flags_decoded.push("KEY_E1".to_string()); }
-        
-        Self {
-            make_code,
-            make_code_hex: format!("0x{:02X}", make_code),
-            flags,
-            flags_decoded,
+    // fails on options the subcommand does not understand
+    fn finish(&self, known: &[&str]) -> Result<(), String> {
+        match self.options.iter().find(|(name, _)| !known.contains(&name.as_str())) {
+            Some((name, _)) => Err(format!("unknown option '{}'", name)),
+            None => Ok(()),
         }
     }
-    
-    fn is_empty(&self) -> bool {
-        self.make_code == 0 && self.flags == 0
-    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ConfigEntryJson {
-    index: u32,    
-    left_ctrl: String,
-    left_alt: String,
-    search: String,
-    assistant: String,
-    left_shift: String,
-    right_ctrl: String,
-    right_alt: String,
-    right_shift: String,
-    
-    original_key: RemapCfgKeyJson,
-    remap_vivaldi_to_fn: bool,
-    
-    remapped_key: Option<RemapCfgKeyJson>,
-    
-    additional_keys: Vec<RemapCfgKeyJson>,
-}
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let Some(subcommand) = args.first() else {
+        return Err("missing subcommand".to_string());
+    };
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(Command::Help);
+    }
 
-fn is_no_detect(s: &String) -> bool {
-    s == "NoDetect"
+    let mut args = Args::parse(&args[1..])?;
+    let output = args.output.clone().unwrap_or_else(|| "-".to_string());
+    let command = match subcommand.as_str() {
+        "decode" => Command::Decode { input: args.input("decode")?, output },
+        "encode" => Command::Encode { input: args.input("encode")?, output },
+        "compile" => Command::Compile { input: args.input("compile")?, output },
+        "decompile" => Command::Decompile { input: args.input("decompile")?, output },
+        "lint" => Command::Lint { input: args.input("lint")? },
+        "simulate" => {
+            let mut positional = args.positionals("simulate", 1, &["a config file", "events"])?.into_iter();
+            let input = positional.next().unwrap_or_default();
+            let events = positional.next();
+            let top_row = top_row_option(&args)?;
+            Command::Simulate { input, events, top_row }
+        }
+        "explain" => {
+            let mut positional =
+                args.positionals("explain", 2, &["a config file", "a key", "modifiers"])?.into_iter();
+            let input = positional.next().unwrap_or_default();
+            let key = positional.next().unwrap_or_default();
+            let modifiers = positional.next().unwrap_or_default();
+            let top_row = top_row_option(&args)?;
+            Command::Explain { input, key, modifiers, top_row }
+        }
+        "install" => {
+            let input = args.input("install")?;
+            let keep = match args.option("--keep") {
+                Some(keep) => keep.parse().map_err(|_| format!("invalid --keep '{}'", keep))?,
+                None => DEFAULT_KEEP_BACKUPS,
+            };
+            let root = args.option("--root").map(str::to_string);
+            Command::Install { input, root, keep, yes: args.switch("--yes") }
+        }
+        "rollback" => {
+            args.positionals("rollback", 0, &[])?;
+            Command::Rollback { root: args.option("--root").map(str::to_string) }
+        }
+        "detect" => {
+            args.positionals("detect", 0, &[])?;
+            Command::Detect
+        }
+        "repair" => Command::Repair {
+            input: args.input("repair")?,
+            output,
+            report: args.option("--report").map(str::to_string),
+            keep_suspect: args.switch("--keep-suspect"),
+        },
+        "diff" => {
+            let mut positional = args.positionals("diff", 2, &["two config files", "a second config file"])?.into_iter();
+            let old = positional.next().unwrap_or_default();
+            let new = positional.next().unwrap_or_default();
+            let format = args.option("--format").unwrap_or("text").to_string();
+            if format != "text" && format != "json" {
+                return Err(format!("unknown format '{}' (expected text or json)", format));
+            }
+            Command::Diff { old, new, format }
+        }
+        "equiv" => {
+            let mut positional =
+                args.positionals("equiv", 2, &["two config files", "a second config file"])?.into_iter();
+            let left = positional.next().unwrap_or_default();
+            let right = positional.next().unwrap_or_default();
+            let limit = match args.option("--limit") {
+                Some(limit) => limit.parse().map_err(|_| format!("invalid --limit '{}'", limit))?,
+                None => 20,
+            };
+            Command::Equiv { left, right, limit }
+        }
+        "table" => {
+            let input = args.input("table")?;
+            let format = args.option("--format").unwrap_or("csv").to_string();
+            if format != "csv" && format != "json" {
+                return Err(format!("unknown format '{}' (expected csv or json)", format));
+            }
+            let top_row = top_row_option(&args)?;
+            Command::Table { input, output, format, top_row }
+        }
+        "preset" => {
+            let name = args.positionals("preset", 0, &["a preset name"])?.pop();
+            let board = match top_row_option(&args)? {
+                Some(TopRow::Keys(_)) => return Err("preset takes --board, not --top-row".to_string()),
+                board => board,
+            };
+            Command::Preset { name, board, output }
+        }
+        "boards" => {
+            args.positionals("boards", 0, &[])?;
+            Command::Boards
+        }
+        "demo" => {
+            args.positionals("demo", 0, &[])?;
+            Command::Demo { output }
+        }
+        "help" => Command::Help,
+        other => return Err(format!("unknown subcommand '{}'", other)),
+    };
+    let known: &[&str] = match command {
+        Command::Simulate { .. } | Command::Explain { .. } => &["--top-row", "--board", "--sysfs"],
+        Command::Table { .. } => &["--format", "--top-row", "--board", "--sysfs"],
+        Command::Preset { .. } => &["--top-row", "--board", "--sysfs"],
+        Command::Equiv { .. } => &["--limit"],
+        Command::Diff { .. } => &["--format"],
+        Command::Repair { .. } => &["--report", "--keep-suspect"],
+        Command::Install { .. } => &["--root", "--keep", "--yes"],
+        Command::Rollback { .. } => &["--root"],
+        _ => &[],
+    };
+    args.finish(known)?;
+
+    let writes_file = match command {
+        Command::Preset { name: None, board: None, .. } => false,
+        Command::Decode { .. }
+        | Command::Encode { .. }
+        | Command::Demo { .. }
+        | Command::Preset { .. }
+        | Command::Compile { .. }
+        | Command::Decompile { .. }
+        | Command::Table { .. }
+        | Command::Repair { .. } => true,
+        _ => false,
+    };
+    if args.output.is_some() && !writes_file {
+        return Err(match command {
+            Command::Preset { .. } => "preset without a name or --board lists the presets; -o needs one".to_string(),
+            _ => format!("{} does not write a file; -o is not accepted", subcommand),
+        });
+    }
+    Ok(command)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ConfigFileJson {
-    magic: String,
-    magic_hex: String,
-    valid: bool,
-    remappings: u32,
-    flip_search_and_assistant_on_pixelbook: bool,
-    has_assistant_key: String,
-    is_non_chrome_ec: String,
-    file_size_bytes: usize,
-    expected_size_bytes: usize,
-    configs: Vec<ConfigEntryJson>,
+fn read_input(path: &str) -> Result<Vec<u8>, String> {
+    let result = if path == "-" {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(path)
+    };
+    result.map_err(|e| format!("could not read '{}': {}", path, e))
 }
 
+// reads a config from a binary settings file, JSON or rule text
+fn load_config(path: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    let data = read_input(path)?;
+    if data.starts_with(&CFG_MAGIC.to_le_bytes()) {
+        return Ok(Config::from_bytes(&data)?);
+    }
 
-
-// config generators
-
-pub fn generate_config_from_json(json_data: &str) -> Result<(), Box<dyn std::error::Error>> {
-    
-    let config_json: ConfigFileJson = serde_json::from_str(&json_data)?;
-    println!("Loaded {} configuration entries from JSON", config_json.configs.len());
-    
-    // validate
-    if config_json.configs.len() > 255 {
-        return Err("Too many configurations (max 255)".into());
+    let text = String::from_utf8(data).map_err(|_| format!("'{}' is not a settings file, JSON or rule text", path))?;
+    if text.trim_start().starts_with('{') {
+        Ok(Config::from_json(&text)?)
+    } else {
+        Ok(dsl::compile(&text)?)
     }
-    
-    let num_configs = config_json.configs.len();
-    let header_size = mem::size_of::<RemapCfgsHeader>();
-    let cfg_size = mem::size_of::<RemapCfg>();
-    let total_size = header_size + cfg_size * num_configs;
-    
-    println!("Generating binary config:");
-    println!("  Header size: {} bytes", header_size);
-    println!("  Config entry size: {} bytes", cfg_size);
-    println!("  Number of configs: {}", num_configs);
-    println!("  Total size: {} bytes\n", total_size);
-    
-    let mut buffer = vec![0u8; total_size];
-    
-    // Write header
-    unsafe {
-        let header = buffer.as_mut_ptr() as *mut RemapCfgsHeader;
-        (*header).magic = CFG_MAGIC;
-        (*header).remappings = num_configs as u32;
-        (*header).flip_search_and_assistant_on_pixelbook = if config_json.flip_search_and_assistant_on_pixelbook { 1 } else { 0 };
-        (*header).has_assistant_key = match config_json.has_assistant_key.as_str() {
-            "Enable" => REMAP_ENABLE,
-            "Disable" => REMAP_DISABLE,
-            _ => REMAP_AUTO_DETECT,
-        };
-        (*header).is_non_chrome_ec = match config_json.is_non_chrome_ec.as_str() {
-            "Enable" => REMAP_ENABLE,
-            "Disable" => REMAP_DISABLE,
-            _ => REMAP_AUTO_DETECT,
-        };
+}
+
+// the Windows installation to install into
+fn install_root(root: Option<String>) -> Result<String, String> {
+    match root {
+        Some(root) => Ok(root),
+        None if cfg!(windows) => Ok("C:\\".to_string()),
+        None => {
+            let roots = find_windows_roots(&MOUNT_BASES);
+            match roots.as_slice() {
+                [root] => {
+                    eprintln!("Using the Windows installation mounted at {}", root.display());
+                    Ok(root.display().to_string())
+                }
+                [] => Err(format!("no mounted Windows installation found under {}; pass --root", MOUNT_BASES.join(", "))),
+                _ => {
+                    let list: Vec<String> = roots.iter().map(|root| root.display().to_string()).collect();
+                    Err(format!("several Windows installations are mounted ({}); pass --root", list.join(", ")))
+                }
+            }
+        }
     }
-    
-    // get mutable slice for configs
-    let cfg_array = unsafe {
-        let ptr = buffer.as_mut_ptr().add(header_size);
-        std::slice::from_raw_parts_mut(ptr as *mut RemapCfg, num_configs)
+}
+
+// prints the config currently installed at `path`, if any; returns whether there is one
+fn show_installed(path: &Path) -> bool {
+    let Ok(data) = fs::read(path) else {
+        return false;
     };
-    
-    // parse and write each config entry
-    for (i, json_config) in config_json.configs.iter().enumerate() {
-        let mut cfg = RemapCfg::new();
-        
-        // parse modifier states
-        cfg.left_ctrl = parse_key_state(&json_config.left_ctrl);
-        cfg.left_alt = parse_key_state(&json_config.left_alt);
-        cfg.search = parse_key_state(&json_config.search);
-        cfg.assistant = parse_key_state(&json_config.assistant);
-        cfg.left_shift = parse_key_state(&json_config.left_shift);
-        cfg.right_ctrl = parse_key_state(&json_config.right_ctrl);
-        cfg.right_alt = parse_key_state(&json_config.right_alt);
-        cfg.right_shift = parse_key_state(&json_config.right_shift);
-        
-        // parse original key
-        cfg.original_key = RemapCfgKey::with_values(
-            json_config.original_key.make_code,
-            json_config.original_key.flags,
-        );
-        
-        // parse remap flag
-        cfg.remap_vivaldi_to_fn_keys = if json_config.remap_vivaldi_to_fn { 1 } else { 0 };
-        
-        // parse remapped key
-        if let Some(ref remapped) = json_config.remapped_key {
-            cfg.remapped_key = RemapCfgKey::with_values(
-                remapped.make_code,
-                remapped.flags,
+    eprintln!("Installed config at {}:", path.display());
+    match Config::from_bytes(&data) {
+        Ok(config) => {
+            let header = &config.header;
+            eprintln!(
+                "  {} entries, flip_search_and_assistant_on_pixelbook = {}, has_assistant_key = {}, is_non_chrome_ec = {}",
+                config.entries.len(),
+                header.flip_search_and_assistant_on_pixelbook,
+                header.has_assistant_key,
+                header.is_non_chrome_ec
             );
-        }
-        
-        // parse additional keys
-        for (j, add_key) in json_config.additional_keys.iter().enumerate() {
-            if j < 8 {
-                cfg.additional_keys[j] = RemapCfgKey::with_values(
-                    add_key.make_code,
-                    add_key.flags,
-                );
+            for (i, rule) in config.entries.iter().enumerate() {
+                eprintln!("  {:>3}: {}", i, dsl::format_rule(rule));
             }
         }
-        
-        cfg_array[i] = cfg;
+        Err(e) => eprintln!("  could not be decoded: {}", e),
     }
-    
-    // write to file
-    let mut file = File::create("croskbsettingsrs.bin")?;
-    println!("read json");
-    file.write_all(&buffer)?;
+    true
+}
 
-        
-    Ok(())
+fn confirm(question: &str) -> Result<bool, String> {
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).map_err(|e| format!("could not read the answer: {}", e))?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
 }
 
-fn parse_key_state(state_str: &str) -> i32 {
-    match state_str {
-        "Enforce" => KEY_STATE_ENFORCE,
-        "EnforceNot" => KEY_STATE_ENFORCE_NOT,
-        _ => KEY_STATE_NO_DETECT,
-    }
+// a top row given with --top-row, --board or --board auto
+enum TopRow {
+    Keys(String),
+    Board(String),
+    Detect(PathBuf),
 }
 
-pub fn read_config(path: &str) -> String {
-    // read file
-    let data = match fs::read(path) {
-        Ok(d) => d,
-        Err(e) => {
-            println!("Error reading file: {}", e);
-            return String::new();
+fn top_row_option(args: &Args) -> Result<Option<TopRow>, String> {
+    let board = args.option("--board");
+    let sysfs = args.option("--sysfs");
+    match (args.option("--top-row"), board, sysfs) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+            Err("--top-row cannot be combined with --board or --sysfs".to_string())
         }
-    };
-
-    // validate minimum file size
-    if data.len() < 17 {
-        println!("File too small (need at least 17 bytes for header, got {})", data.len());
-        return String::new();
+        (Some(keys), None, None) => Ok(Some(TopRow::Keys(keys.to_string()))),
+        (None, Some(board), None) if board != "auto" => Ok(Some(TopRow::Board(board.to_string()))),
+        (None, Some(_), Some(_)) if board != Some("auto") => {
+            Err("--sysfs detects the board; use it with --board auto or alone".to_string())
+        }
+        (None, None, None) => Ok(None),
+        _ => Ok(Some(TopRow::Detect(PathBuf::from(sysfs.unwrap_or(DEFAULT_SYSFS_ROOT))))),
     }
-    
-    // Read magic (0x0000-0x0003)
-    let magic_bytes = &data[0..4];
-    let magic_u32 = bytes_to_u32(magic_bytes).unwrap();
-    let magic_str = String::from_utf8_lossy(magic_bytes).to_string();
+}
 
-    let valid = magic_u32 == CFG_MAGIC;
+// reads the board from a sysfs tree and reports what was found
+fn detect(root: &Path) -> Result<DetectedBoard, config_generator::Error> {
+    let detected = detect_board(root)?;
+    let source = match &detected.physmap {
+        Some(path) => format!("top row from {}", path.display()),
+        None => "top row from the board list".to_string(),
+    };
+    eprintln!("Detected board {} ({}, {} keys)", detected.name(), source, detected.top_row.len());
+    Ok(detected)
+}
 
-    if !valid {
-        println!("Invalid magic number: expected 0x{:08X}, got 0x{:08X} ('{}')", 
-                 CFG_MAGIC, magic_u32, magic_str);
-        return String::new();
+// parses a comma-separated --top-row list, or looks up or detects the --board
+fn parse_top_row(top_row: &TopRow) -> Result<Vec<RemapCfgKey>, config_generator::Error> {
+    match top_row {
+        TopRow::Keys(keys) => keys.split(',').map(|key| parse_key(key.trim())).collect(),
+        TopRow::Board(name) => Ok(boards::board(name)?.top_row),
+        TopRow::Detect(root) => Ok(detect(root)?.top_row),
     }
+}
 
-    println!("Valid CrosKB settings file");
-    println!("  Magic: '{}' (0x{:08X})", magic_str, magic_u32);
-
-    // Read remappings (0x0004-0x0007)
-    let remappings = bytes_to_u32(&data[4..8]).unwrap();
-    println!("  Number of remappings: {}", remappings);
-
-    // Read flip_search_assistant (0x0008)
-    let flip_search_assistant = data[8] != 0;
-    println!("  Flip search and assistant: {}", flip_search_assistant);
-
-    // Read has_assistant_key (0x0009-0x000C)
-    let has_assistant_key = bytes_to_i32(&data[0x0009..0x000D]).unwrap();
-    let has_assistant_str = match has_assistant_key {
-        0 => "AutoDetect",
-        1 => "Enable",
-        2 => "Disable",
-        _ => "Unknown",
-    };
-    println!("  Has assistant key: {} ({})", has_assistant_key, has_assistant_str);
-
-    // Read is_non_chrome_ec (0x000D-0x0010)
-    let is_non_chrome_ec = bytes_to_i32(&data[0x000D..0x0011]).unwrap();
-    let is_non_chrome_ec_str = match is_non_chrome_ec {
-        0 => "AutoDetect",
-        1 => "Enable",
-        2 => "Disable",
-        _ => "Unknown",
+fn write_output(path: &str, data: &[u8]) -> Result<(), String> {
+    let result = if path == "-" {
+        let mut stdout = io::stdout().lock();
+        match stdout.write_all(data).and_then(|_| stdout.flush()) {
+            // the reader stopped early, e.g. `| head`
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    } else {
+        File::create(Path::new(path)).and_then(|mut file| file.write_all(data))
     };
-    println!("  Is non-Chrome EC: {} ({})", is_non_chrome_ec, is_non_chrome_ec_str);
-
-    println!("\nConfiguration Entries\n");
-
-    let expected_size = 17 + (remappings as usize * 73);
-    if data.len() < expected_size {
-        println!("Warning: File size mismatch. Expected {} bytes, got {} bytes", 
-                 expected_size, data.len());
-    }
+    result.map_err(|e| format!("could not write '{}': {}", path, e))
+}
 
-    let available_configs = (data.len() - 17) / 73;
-    let configs_to_read = remappings.min(available_configs as u32);
-
-    let mut configs = Vec::new();
-
-    for i in 0..configs_to_read {
-        let offset = 17 + (i as usize * 73);
-        
-        if offset + 73 > data.len() {
-            println!("Config {}: Incomplete (not enough data)", i);
-            break;
-        }
-
-        let config_data = &data[offset..offset + 73];
-        
-        // Read modifier states
-        let left_ctrl = bytes_to_i32(&config_data[0x00..0x04]).unwrap();
-        let left_alt = bytes_to_i32(&config_data[0x04..0x08]).unwrap();
-        let search = bytes_to_i32(&config_data[0x08..0x0C]).unwrap();
-        let assistant = bytes_to_i32(&config_data[0x0C..0x10]).unwrap();
-        let left_shift = bytes_to_i32(&config_data[0x10..0x14]).unwrap();
-        let right_ctrl = bytes_to_i32(&config_data[0x14..0x18]).unwrap();
-        let right_alt = bytes_to_i32(&config_data[0x18..0x1C]).unwrap();
-        let right_shift = bytes_to_i32(&config_data[0x1C..0x20]).unwrap();
-
-        // Read original key
-        let orig_make_code = bytes_to_u16(&config_data[0x20..0x22]).unwrap();
-        let orig_flags = bytes_to_u16(&config_data[0x22..0x24]).unwrap();
-
-        // Read remap flag
-        let remap_vivaldi = config_data[0x24] != 0;
-
-        // Read remapped key
-        let remap_make_code = bytes_to_u16(&config_data[0x25..0x27]).unwrap();
-        let remap_flags = bytes_to_u16(&config_data[0x27..0x29]).unwrap();
-
-        // Read additional keys
-        let mut additional_keys_vec = Vec::new();
-        for j in 0..8 {
-            let key_offset = 0x29 + (j * 4);
-            let make_code = bytes_to_u16(&config_data[key_offset..key_offset + 2]).unwrap();
-            let flags = bytes_to_u16(&config_data[key_offset + 2..key_offset + 4]).unwrap();
-            
-            if make_code != 0 || flags != 0 {
-                additional_keys_vec.push(RemapCfgKeyJson::new(make_code, flags));
+fn run(command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Decode { input, output } => {
+            let data = read_input(&input)?;
+            let config = Config::from_bytes(&data)?;
+            print_config(&config, data.len());
+            write_output(&output, config.to_json()?.as_bytes())?;
+        }
+        Command::Encode { input, output } => {
+            let json = String::from_utf8(read_input(&input)?)?;
+            let config = Config::from_json(&json)?;
+            eprintln!("Loaded {} configuration entries from JSON", config.entries.len());
+            eprintln!("Generating binary config:");
+            eprintln!("  Header size: {} bytes", HEADER_SIZE);
+            eprintln!("  Config entry size: {} bytes", CFG_SIZE);
+            eprintln!("  Number of configs: {}", config.entries.len());
+            eprintln!("  Total size: {} bytes\n", config.encoded_len());
+            write_output(&output, &config.to_bytes())?;
+        }
+        Command::Demo { output } => {
+            let data = generate_demo_config();
+            write_output(&output, &data)?;
+        }
+        Command::Preset { name: None, board: None, .. } => {
+            for preset in presets::PRESETS {
+                println!("{:<16} {}", preset.name, preset.description);
             }
         }
-
-        // Create JSON entry
-        let remapped_key_obj = RemapCfgKeyJson::new(remap_make_code, remap_flags);
-        
-        let config_entry = ConfigEntryJson {
-            index: i,
-            left_ctrl: format_key_state(left_ctrl).to_string(),
-            left_alt: format_key_state(left_alt).to_string(),
-            search: format_key_state(search).to_string(),
-            assistant: format_key_state(assistant).to_string(),
-            left_shift: format_key_state(left_shift).to_string(),
-            right_ctrl: format_key_state(right_ctrl).to_string(),
-            right_alt: format_key_state(right_alt).to_string(),
-            right_shift: format_key_state(right_shift).to_string(),
-            original_key: RemapCfgKeyJson::new(orig_make_code, orig_flags),
-            remap_vivaldi_to_fn: remap_vivaldi,
-            remapped_key: if remapped_key_obj.is_empty() { None } else { Some(remapped_key_obj) },
-            additional_keys: additional_keys_vec.clone(),
-        };
-
-        // Print config entry
-        println!("Config Entry {}:", i);
-        println!("  File offset: 0x{:04X}", offset);
-        
-        // Print modifiers if not NoDetect (0)
-        let mut modifiers = Vec::new();
-        if left_ctrl != 0 { modifiers.push(format!("LeftCtrl={}", format_key_state(left_ctrl))); }
-        if left_alt != 0 { modifiers.push(format!("LeftAlt={}", format_key_state(left_alt))); }
-        if search != 0 { modifiers.push(format!("Search={}", format_key_state(search))); }
-        if assistant != 0 { modifiers.push(format!("Assistant={}", format_key_state(assistant))); }
-        if left_shift != 0 { modifiers.push(format!("LeftShift={}", format_key_state(left_shift))); }
-        if right_ctrl != 0 { modifiers.push(format!("RightCtrl={}", format_key_state(right_ctrl))); }
-        if right_alt != 0 { modifiers.push(format!("RightAlt={}", format_key_state(right_alt))); }
-        if right_shift != 0 { modifiers.push(format!("RightShift={}", format_key_state(right_shift))); }
-        
-        if !modifiers.is_empty() {
-            println!("  Modifiers: {}", modifiers.join(", "));
+        Command::Preset { name, board, output } => {
+            let name = name.unwrap_or_else(|| "default".to_string());
+            let mut config = presets::preset(&name)?;
+            match board {
+                Some(TopRow::Board(board)) => {
+                    config = boards::board(&board)?.configure(&config)?;
+                    eprintln!("Preset '{}' for {}: {} entries", name, board, config.entries.len());
+                }
+                Some(TopRow::Detect(root)) => {
+                    let detected = detect(&root)?;
+                    config = detected.configure(&config)?;
+                    eprintln!("Preset '{}' for {}: {} entries", name, detected.name(), config.entries.len());
+                }
+                _ => eprintln!("Preset '{}': {} entries", name, config.entries.len()),
+            }
+            write_output(&output, &config.to_bytes())?;
         }
-
-        println!("  Original key: 0x{:02X} (flags: {})", orig_make_code, format_flags(orig_flags));
-        
-        if remap_vivaldi {
-            println!("  Remap to: Vivaldi -> Function key");
-        } else if remap_make_code != 0 || remap_flags != 0 {
-            println!("  Remap to: 0x{:02X} (flags: {})", remap_make_code, format_flags(remap_flags));
+        Command::Boards => {
+            for board in boards::boards() {
+                let keys: Vec<String> = board.top_row.iter().map(|&key| format_key(key).unwrap_or_else(|| key.to_string())).collect();
+                let assistant = if board.has_assistant_key { " (assistant key)" } else { "" };
+                println!("{}{}: {}", board.name, assistant, keys.join(", "));
+            }
         }
-
-        if !additional_keys_vec.is_empty() {
-            println!("  Additional keys:");
-            for (idx, key) in additional_keys_vec.iter().enumerate() {
-                println!("    [{}] 0x{:02X} (flags: {})", idx, key.make_code, 
-                         if key.flags_decoded.is_empty() { "NONE".to_string() } else { key.flags_decoded.join("|") });
+        Command::Compile { input, output } => {
+            let text = String::from_utf8(read_input(&input)?)?;
+            let config = dsl::compile(&text)?;
+            eprintln!("Compiled {} rules", config.entries.len());
+            write_output(&output, &config.to_bytes())?;
+        }
+        Command::Decompile { input, output } => {
+            let config = Config::from_bytes(&read_input(&input)?)?;
+            write_output(&output, dsl::decompile(&config).as_bytes())?;
+        }
+        Command::Lint { input } => {
+            let config = load_config(&input)?;
+            let issues = lint::lint(&config);
+            let text: String = issues.iter().map(|issue| format!("{}\n", issue)).collect();
+            write_output("-", text.as_bytes())?;
+            let errors = issues.iter().filter(|issue| issue.kind.is_error()).count();
+            eprintln!("{} entries checked: {} errors, {} warnings", config.entries.len(), errors, issues.len() - errors);
+            if errors > 0 {
+                std::process::exit(1);
             }
         }
-
-        println!();
-        
-        configs.push(config_entry);
-    }
-
-    println!("Successfully read {} configuration entries", configs_to_read);
-
-    // Create and write JSON output
-    let json_output = ConfigFileJson {
-        magic: magic_str,
-        magic_hex: format!("0x{:08X}", magic_u32),
-        valid,
-        remappings,
-        flip_search_and_assistant_on_pixelbook: flip_search_assistant,
-        has_assistant_key: has_assistant_str.to_string(),
-        is_non_chrome_ec: is_non_chrome_ec_str.to_string(),
-        file_size_bytes: data.len(),
-        expected_size_bytes: expected_size,
-        configs,
-    };
-
-    match serde_json::to_string_pretty(&json_output) {
-        Ok(json_string) => {
-            return json_string;
+        Command::Simulate { input, events, top_row } => {
+            let events = match events {
+                Some(events) => events,
+                None if input == "-" => return Err("simulate cannot read both the config and the events from stdin".into()),
+                None => String::from_utf8(read_input("-")?)?,
+            };
+            let config = load_config(&input)?;
+            let mut simulator = match top_row {
+                Some(top_row) => Simulator::with_top_row(&config, parse_top_row(&top_row)?),
+                None => Simulator::new(&config),
+            };
+            let mut text = String::new();
+            for event in parse_events(&events)? {
+                // the entry applied to this key, looked up while it is still held
+                let released = if is_break(event) { simulator.active_entry(event) } else { None };
+                let output = simulator.process(event);
+                let entry = released.or_else(|| simulator.active_entry(event));
+                let sent: Vec<String> = output.into_iter().map(format_event).collect();
+                text += &match entry {
+                    Some(i) => format!("{:<16} -> {:<40} # config {}\n", format_event(event), sent.join(", "), i),
+                    None => format!("{:<16} -> {}\n", format_event(event), sent.join(", ")),
+                };
+            }
+            write_output("-", text.as_bytes())?;
         }
-        Err(e) => {
-            println!("\nError serializing to JSON: {}", e);
-            return String::new();
+        Command::Explain { input, key, modifiers, top_row } => {
+            let config = load_config(&input)?;
+            let key = parse_key(&key)?;
+            let held: ModifierSet = modifiers.parse()?;
+            let explanation = match top_row {
+                Some(top_row) => explain_with_top_row(&config, parse_top_row(&top_row)?, key, held),
+                None => explain(&config, key, held),
+            };
+            print!("{}", explanation);
         }
+        Command::Table { input, output, format, top_row } => {
+            let config = load_config(&input)?;
+            let rows = match top_row {
+                Some(top_row) => table::mapping_table_with_top_row(&config, &parse_top_row(&top_row)?),
+                None => table::mapping_table(&config),
+            };
+            eprintln!("{} rows ({} keys x 256 modifier states)", rows.len(), rows.len() / 256);
+            let text = if format == "json" { table::to_json(&rows)? } else { table::to_csv(&rows) };
+            write_output(&output, text.as_bytes())?;
+        }
+        Command::Install { input, root, keep, yes } => {
+            let installer = Installer::new(install_root(root)?).keep_backups(keep);
+            let config = load_config(&input)?;
+            // refuse an invalid file before asking about the installed one
+            config.validate()?;
+            let data = config.to_bytes();
+            let interactive = !yes && input != "-" && io::stdin().is_terminal();
+            if show_installed(&installer.target()) && interactive && !confirm("Overwrite it?")? {
+                eprintln!("Nothing installed");
+                return Ok(());
+            }
+            let outcome = installer.install(&data)?;
+            if let Some(backup) = &outcome.backup {
+                eprintln!("Backed up the previous file to {}", backup.display());
+            }
+            for path in &outcome.removed_backups {
+                eprintln!("Removed old backup {}", path.display());
+            }
+            eprintln!("Installed {} bytes to {}", data.len(), outcome.target.display());
+        }
+        Command::Detect => {
+            let roots = find_windows_roots(&MOUNT_BASES);
+            if roots.is_empty() {
+                eprintln!("No mounted Windows installation found under {}", MOUNT_BASES.join(", "));
+            }
+            for root in roots {
+                println!("{}", root.display());
+                if !show_installed(&Installer::new(&root).target()) {
+                    eprintln!("  no croskbsettings.bin installed");
+                }
+            }
+        }
+        Command::Rollback { root } => {
+            let installer = Installer::new(install_root(root)?);
+            let outcome = installer.rollback()?;
+            if let Some(undo) = &outcome.undo {
+                eprintln!("Saved the replaced file as {}", undo.display());
+            }
+            eprintln!("Restored {} to {}", outcome.restored.display(), installer.target().display());
+        }
+        Command::Repair { input, output, report, keep_suspect } => {
+            let (config, summary) = repair(&read_input(&input)?, RepairOptions { keep_suspect })?;
+            eprint!("{}", summary);
+            if summary.is_clean() {
+                eprintln!("No problems found");
+            }
+            if let Some(path) = report {
+                write_output(&path, summary.to_json()?.as_bytes())?;
+            }
+            write_output(&output, &config.to_bytes())?;
+        }
+        Command::Diff { old, new, format } => {
+            let changes = diff::diff(&load_config(&old)?, &load_config(&new)?);
+            let text = if format == "json" { format!("{}\n", changes.to_json()?) } else { changes.to_string() };
+            write_output("-", text.as_bytes())?;
+            if !changes.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Command::Equiv { left, right, limit } => {
+            let result = check_equivalence(&load_config(&left)?, &load_config(&right)?);
+            let mut text = String::new();
+            for difference in &result.header {
+                text += &format!("header {}\n", difference);
+            }
+            for counterexample in result.counterexamples.iter().take(limit) {
+                text += &format!("{}\n", counterexample);
+            }
+            if result.counterexamples.len() > limit {
+                text += &format!("... and {} more\n", result.counterexamples.len() - limit);
+            }
+            write_output("-", text.as_bytes())?;
+            if result.is_equivalent() {
+                eprintln!("'{}' and '{}' are equivalent", left, right);
+            } else {
+                eprintln!(
+                    "'{}' and '{}' differ: {} header settings, {} key and modifier states",
+                    left,
+                    right,
+                    result.header.len(),
+                    result.counterexamples.len()
+                );
+                std::process::exit(1);
+            }
+        }
+        Command::Help => println!("{}", USAGE),
     }
+    Ok(())
 }
 
-//helper
-fn bytes_to_u32(bytes: &[u8]) -> Option<u32> {
-    if bytes.len() < 4 {
-        return None;
-    }
-    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
-}
-
-fn bytes_to_i32(bytes: &[u8]) -> Option<i32> {
-    if bytes.len() < 4 {
-        return None;
-    }
-    Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
-}
-
-fn bytes_to_u16(bytes: &[u8]) -> Option<u16> {
-    if bytes.len() < 2 {
-        return None;
-    }
-    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
-}
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-fn format_key_state(state: i32) -> &'static str {
-    match state {
-        0 => "NoDetect",
-        1 => "Enforce",
-        2 => "EnforceNot",
-        _ => "Unknown",
-    }
-}
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
 
-fn format_flags(flags: u16) -> String {
-    let mut flag_strs = Vec::new();
-    
-    if flags & 0x0001 != 0 { flag_strs.push("BREAK"); }
-    if flags & 0x0002 != 0 { flag_strs.push("E0"); }
-    if flags & 0x0004 != 0 { flag_strs.push("E1"); }
-    
-    if flag_strs.is_empty() {
-        "NONE".to_string()
-    } else {
-        flag_strs.join("|")
+    if let Err(e) = run(command) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
 }
 
-fn main()
-{
-    let path = "croskbsettings.bin";
-    let output = read_config(path);
-    let _ = generate_config_from_json(&output).unwrap();
-    
-}
-
 // demo functions
 
-fn generate_demo_config(num_remaps: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Creating Vivaldi keyboard configuration...\n");
-    
-    let config_data = demo_config()?;
-    
-    println!("Generated config size: {} bytes", config_data.len());
-    println!("Expected: 17 (header) + 73 * 40 (configs) = 2937 bytes\n");
+fn generate_demo_config() -> Vec<u8> {
+    eprintln!("Creating Vivaldi keyboard configuration...\n");
     
-    let mut file = File::create("croskbsettingsrs.bin")?;
-    file.write_all(&config_data)?;
+    let config_data = demo_config();
     
-    println!("Successfully wrote {} bytes to croskbsettingsrs.bin", config_data.len());
+    eprintln!("Generated config size: {} bytes", config_data.len());
+    eprintln!("Expected: 17 (header) + 73 * 40 (configs) = 2937 bytes\n");
     
-    Ok(())
+    config_data
 }
 
-fn demo_config() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    const NUM_CONFIGS: usize = 40;
-    
-    let header_size = mem::size_of::<RemapCfgsHeader>();
-    let cfg_size = mem::size_of::<RemapCfg>();
-    let total_size = header_size + cfg_size * NUM_CONFIGS;
+// prints a human-readable description of a decoded file to stderr
+fn print_config(config: &Config, file_size: usize) {
+    let header = &config.header;
 
-    println!("Header size: {} (should be 17)", header_size);
-    println!("RemapCfg size: {} (should be 73)", cfg_size);
-    println!("Total size: {} bytes", total_size);
+    eprintln!("Valid CrosKB settings file");
+    eprintln!("  Magic: '{}' (0x{:08X})", String::from_utf8_lossy(&CFG_MAGIC.to_le_bytes()), CFG_MAGIC);
+    eprintln!("  File size: {} bytes", file_size);
+    eprintln!("  Number of remappings: {}", header.remappings);
+    eprintln!("  Flip search and assistant: {}", header.flip_search_and_assistant_on_pixelbook);
+    eprintln!("  Has assistant key: {} ({})", i32::from(header.has_assistant_key), header.has_assistant_key);
+    eprintln!("  Is non-Chrome EC: {} ({})", i32::from(header.is_non_chrome_ec), header.is_non_chrome_ec);
 
-    if header_size != 17 {
-        return Err(format!("ERROR: Header size is {}, expected 17!", header_size).into());
+    eprintln!("\nConfiguration Entries\n");
+
+    if !config.trailing.is_empty() {
+        eprintln!("Note: {} bytes after the last entry are kept as trailing data", config.trailing.len());
     }
-    if cfg_size != 73 {
-        return Err(format!("ERROR: RemapCfg size is {}, expected 73!", cfg_size).into());
+    if let Err(e) = config.validate() {
+        eprintln!("Warning: {}; the value is kept as is", e);
     }
 
-    let mut buffer = vec![0u8; total_size];
-    
-    // Write header
-    unsafe {
-        let header = buffer.as_mut_ptr() as *mut RemapCfgsHeader;
-        (*header).magic = CFG_MAGIC;
-        (*header).remappings = NUM_CONFIGS as u32;
-        (*header).flip_search_and_assistant_on_pixelbook = 1;
-        (*header).has_assistant_key = REMAP_AUTO_DETECT;
-        (*header).is_non_chrome_ec = REMAP_AUTO_DETECT;
-    }
+    for (i, rule) in config.entries.iter().enumerate() {
+        eprintln!("Config Entry {}:", i);
+        eprintln!("  File offset: 0x{:04X}", HEADER_SIZE + i * CFG_SIZE);
 
-    let cfg_array = unsafe {
-        let ptr = buffer.as_mut_ptr().add(header_size);
-        std::slice::from_raw_parts_mut(ptr as *mut RemapCfg, NUM_CONFIGS)
-    };
+        // Print modifiers if not NoDetect (0)
+        let states = [
+            ("LeftCtrl", rule.left_ctrl),
+            ("LeftAlt", rule.left_alt),
+            ("Search", rule.search),
+            ("Assistant", rule.assistant),
+            ("LeftShift", rule.left_shift),
+            ("RightCtrl", rule.right_ctrl),
+            ("RightAlt", rule.right_alt),
+            ("RightShift", rule.right_shift),
+        ];
+        let modifiers: Vec<String> = states
+            .iter()
+            .filter(|(_, state)| *state != KeyState::NoDetect)
+            .map(|(name, state)| format!("{}={}", name, state))
+            .collect();
 
-    for cfg in cfg_array.iter_mut() {
-        *cfg = RemapCfg::new();
-    }
+        if !modifiers.is_empty() {
+            eprintln!("  Modifiers: {}", modifiers.join(", "));
+        }
+
+        let original = rule.original_key;
+        eprintln!("  Original key: {} (0x{:02X}, flags: {})", original, { original.make_code }, format_flags(original.flags));
+
+        let remapped = rule.remapped_key;
+        if rule.remap_vivaldi_to_fn_keys {
+            eprintln!("  Remap to: Vivaldi -> Function key");
+        } else if !remapped.is_empty() {
+            eprintln!("  Remap to: {} (0x{:02X}, flags: {})", remapped, { remapped.make_code }, format_flags(remapped.flags));
+        }
 
-    // Map Vivaldi keys (without Ctrl) to F# keys (configs 0-18)
-    let vivaldi_keys = [
-        VIVALDI_BACK, VIVALDI_FWD, VIVALDI_REFRESH, VIVALDI_FULLSCREEN,
-        VIVALDI_OVERVIEW, VIVALDI_SNAPSHOT, VIVALDI_BRIGHTNESS_DN,
-        VIVALDI_BRIGHTNESS_UP, VIVALDI_PRIVACY_TOGGLE, VIVALDI_KBD_BKLIGHT_DOWN,
-        VIVALDI_KBD_BKLIGHT_UP, VIVALDI_KBD_BKLIGHT_TOGGLE, VIVALDI_PLAY_PAUSE,
-        VIVALDI_MUTE, VIVALDI_VOL_DN, VIVALDI_VOL_UP, VIVALDI_NEXT_TRACK,
-        VIVALDI_PREV_TRACK, VIVALDI_MIC_MUTE,
-    ];
-
-    for (i, &key) in vivaldi_keys.iter().enumerate() {
-        cfg_array[i].left_ctrl = KEY_STATE_ENFORCE_NOT;
-        cfg_array[i].original_key = RemapCfgKey::with_values(key, KEY_E0);
-        cfg_array[i].remap_vivaldi_to_fn_keys = 1;
+        // every used slot, including any after an empty one
+        let additional_keys: Vec<_> =
+            rule.additional_keys.iter().enumerate().filter(|(_, key)| !key.is_empty()).collect();
+        if !additional_keys.is_empty() {
+            eprintln!("  Additional keys:");
+            for (idx, key) in additional_keys {
+                eprintln!("    [{}] {} (0x{:02X}, flags: {})", idx, key, { key.make_code }, format_flags(key.flags));
+            }
+        }
+
+        eprintln!();
     }
 
-    // Config 19: Ctrl + Alt + Backspace -> Ctrl + Alt + Delete
-    cfg_array[19].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[19].left_alt = KEY_STATE_ENFORCE;
-    cfg_array[19].original_key = RemapCfgKey::with_values(K_BACKSP, 0);
-    cfg_array[19].remapped_key = RemapCfgKey::with_values(K_DELETE, KEY_E0);
-
-    // Config 20: Ctrl + Backspace -> Delete
-    cfg_array[20].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[20].left_alt = KEY_STATE_ENFORCE_NOT;
-    cfg_array[20].original_key = RemapCfgKey::with_values(K_BACKSP, 0);
-    cfg_array[20].remapped_key = RemapCfgKey::with_values(K_DELETE, KEY_E0);
-    cfg_array[20].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    // Config 21: Ctrl + Fullscreen -> F11
-    cfg_array[21].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[21].left_shift = KEY_STATE_ENFORCE_NOT;
-    cfg_array[21].original_key = RemapCfgKey::with_values(VIVALDI_FULLSCREEN, KEY_E0);
-    cfg_array[21].remapped_key = RemapCfgKey::with_values(FUNCTION_KEYS[10], 0);
-    cfg_array[21].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    // Config 22: Ctrl + Shift + Fullscreen -> Windows + p
-    cfg_array[22].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[22].left_shift = KEY_STATE_ENFORCE;
-    cfg_array[22].search = KEY_STATE_ENFORCE_NOT;
-    cfg_array[22].original_key = RemapCfgKey::with_values(VIVALDI_FULLSCREEN, KEY_E0);
-    cfg_array[22].remapped_key = RemapCfgKey::with_values(0x19, 0);
-    cfg_array[22].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-    cfg_array[22].additional_keys[1] = RemapCfgKey::with_values(K_LSHFT, KEY_BREAK);
-    cfg_array[22].additional_keys[2] = RemapCfgKey::with_values(K_LWIN, KEY_E0);
-
-    // Config 23
-    cfg_array[23].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[23].left_shift = KEY_STATE_ENFORCE;
-    cfg_array[23].search = KEY_STATE_ENFORCE;
-    cfg_array[23].original_key = RemapCfgKey::with_values(VIVALDI_FULLSCREEN, KEY_E0);
-    cfg_array[23].remapped_key = RemapCfgKey::with_values(0x19, 0);
-    cfg_array[23].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-    cfg_array[23].additional_keys[1] = RemapCfgKey::with_values(K_LSHFT, KEY_BREAK);
-
-    // Config 24: Ctrl + Overview -> Windows + Tab
-    cfg_array[24].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[24].left_shift = KEY_STATE_ENFORCE_NOT;
-    cfg_array[24].search = KEY_STATE_ENFORCE_NOT;
-    cfg_array[24].original_key = RemapCfgKey::with_values(VIVALDI_OVERVIEW, KEY_E0);
-    cfg_array[24].remapped_key = RemapCfgKey::with_values(0x0F, 0);
-    cfg_array[24].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-    cfg_array[24].additional_keys[1] = RemapCfgKey::with_values(K_LWIN, KEY_E0);
-
-    // Config 25
-    cfg_array[25].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[25].left_shift = KEY_STATE_ENFORCE_NOT;
-    cfg_array[25].search = KEY_STATE_ENFORCE;
-    cfg_array[25].original_key = RemapCfgKey::with_values(VIVALDI_OVERVIEW, KEY_E0);
-    cfg_array[25].remapped_key = RemapCfgKey::with_values(0x0F, 0);
-    cfg_array[25].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    // Config 26: Ctrl + Shift + Overview -> Windows + Shift + S
-    cfg_array[26].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[26].left_shift = KEY_STATE_ENFORCE;
-    cfg_array[26].search = KEY_STATE_ENFORCE_NOT;
-    cfg_array[26].original_key = RemapCfgKey::with_values(VIVALDI_OVERVIEW, KEY_E0);
-    cfg_array[26].remapped_key = RemapCfgKey::with_values(0x1F, 0);
-    cfg_array[26].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-    cfg_array[26].additional_keys[1] = RemapCfgKey::with_values(K_LWIN, KEY_E0);
-
-    // Config 27
-    cfg_array[27].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[27].left_shift = KEY_STATE_ENFORCE;
-    cfg_array[27].search = KEY_STATE_ENFORCE;
-    cfg_array[27].original_key = RemapCfgKey::with_values(VIVALDI_OVERVIEW, KEY_E0);
-    cfg_array[27].remapped_key = RemapCfgKey::with_values(0x1F, 0);
-    cfg_array[27].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    // Config 28: Ctrl + Snapshot -> Windows + Shift + S
-    cfg_array[28].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[28].left_shift = KEY_STATE_ENFORCE_NOT;
-    cfg_array[28].search = KEY_STATE_ENFORCE_NOT;
-    cfg_array[28].original_key = RemapCfgKey::with_values(VIVALDI_SNAPSHOT, KEY_E0);
-    cfg_array[28].remapped_key = RemapCfgKey::with_values(0x1F, 0);
-    cfg_array[28].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-    cfg_array[28].additional_keys[1] = RemapCfgKey::with_values(K_LWIN, KEY_E0);
-    cfg_array[28].additional_keys[2] = RemapCfgKey::with_values(K_LSHFT, 0);
-
-    // Config 29
-    cfg_array[29].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[29].left_shift = KEY_STATE_ENFORCE_NOT;
-    cfg_array[29].search = KEY_STATE_ENFORCE;
-    cfg_array[29].original_key = RemapCfgKey::with_values(VIVALDI_SNAPSHOT, KEY_E0);
-    cfg_array[29].remapped_key = RemapCfgKey::with_values(0x1F, 0);
-    cfg_array[29].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-    cfg_array[29].additional_keys[1] = RemapCfgKey::with_values(K_LSHFT, 0);
-
-    // Config 30
-    cfg_array[30].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[30].left_shift = KEY_STATE_ENFORCE;
-    cfg_array[30].search = KEY_STATE_ENFORCE_NOT;
-    cfg_array[30].original_key = RemapCfgKey::with_values(VIVALDI_SNAPSHOT, KEY_E0);
-    cfg_array[30].remapped_key = RemapCfgKey::with_values(0x1F, 0);
-    cfg_array[30].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-    cfg_array[30].additional_keys[1] = RemapCfgKey::with_values(K_LWIN, KEY_E0);
-
-    // Config 31
-    cfg_array[31].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[31].left_shift = KEY_STATE_ENFORCE;
-    cfg_array[31].search = KEY_STATE_ENFORCE;
-    cfg_array[31].original_key = RemapCfgKey::with_values(VIVALDI_SNAPSHOT, KEY_E0);
-    cfg_array[31].remapped_key = RemapCfgKey::with_values(0x1F, 0);
-    cfg_array[31].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    // Config 32-33: Ctrl + Alt + Brightness -> Ctrl + Alt + KB Brightness
-    cfg_array[32].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[32].left_alt = KEY_STATE_ENFORCE;
-    cfg_array[32].original_key = RemapCfgKey::with_values(VIVALDI_BRIGHTNESS_DN, KEY_E0);
-    cfg_array[32].remapped_key = RemapCfgKey::with_values(VIVALDI_KBD_BKLIGHT_DOWN, KEY_E0);
-
-    cfg_array[33].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[33].left_alt = KEY_STATE_ENFORCE;
-    cfg_array[33].original_key = RemapCfgKey::with_values(VIVALDI_BRIGHTNESS_UP, KEY_E0);
-    cfg_array[33].remapped_key = RemapCfgKey::with_values(VIVALDI_KBD_BKLIGHT_UP, KEY_E0);
-
-    // Config 34-37: Ctrl + Arrow keys -> Home/End/PageUp/PageDown
-    cfg_array[34].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[34].original_key = RemapCfgKey::with_values(K_LEFT, KEY_E0);
-    cfg_array[34].remapped_key = RemapCfgKey::with_values(K_HOME, KEY_E0);
-    cfg_array[34].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    cfg_array[35].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[35].original_key = RemapCfgKey::with_values(K_RIGHT, KEY_E0);
-    cfg_array[35].remapped_key = RemapCfgKey::with_values(K_END, KEY_E0);
-    cfg_array[35].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    cfg_array[36].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[36].original_key = RemapCfgKey::with_values(K_UP, KEY_E0);
-    cfg_array[36].remapped_key = RemapCfgKey::with_values(K_PGUP, KEY_E0);
-    cfg_array[36].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    cfg_array[37].left_ctrl = KEY_STATE_ENFORCE;
-    cfg_array[37].original_key = RemapCfgKey::with_values(K_DOWN, KEY_E0);
-    cfg_array[37].remapped_key = RemapCfgKey::with_values(K_PGDN, KEY_E0);
-    cfg_array[37].additional_keys[0] = RemapCfgKey::with_values(K_LCTRL, KEY_BREAK);
-
-    // Config 38-39: Lock -> Windows + L
-    cfg_array[38].search = KEY_STATE_ENFORCE_NOT;
-    cfg_array[38].original_key = RemapCfgKey::with_values(K_LOCK, 0);
-    cfg_array[38].remapped_key = RemapCfgKey::with_values(0x26, 0);
-    cfg_array[38].additional_keys[0] = RemapCfgKey::with_values(K_LWIN, KEY_E0);
-
-    cfg_array[39].search = KEY_STATE_ENFORCE;
-    cfg_array[39].original_key = RemapCfgKey::with_values(K_LOCK, 0);
-    cfg_array[39].remapped_key = RemapCfgKey::with_values(0x26, 0);
-
-    Ok(buffer)
+    eprintln!("Successfully read {} configuration entries", config.entries.len());
 }
-
@@ -0,0 +1,88 @@
+//! Named, ready-made layouts.
+//!
+//! Each preset is rule text (see `dsl`) under `src/presets/`, compiled on
+//! demand, so adding one means adding a `.rules` file and a line below.
+
+use crate::config::Config;
+use crate::dsl;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// The preset's rule text.
+    pub rules: &'static str,
+}
+
+impl Preset {
+    pub fn config(&self) -> Config {
+        dsl::compile(self.rules).unwrap_or_else(|e| panic!("built-in preset '{}' does not compile: {}", self.name, e))
+    }
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "default",
+        description: "coolstar's default layout: F keys on the top row, Ctrl shortcuts for media and Windows",
+        rules: include_str!("presets/default.rules"),
+    },
+    Preset {
+        name: "chromeos-parity",
+        description: "media keys on the top row, Search + top row for F keys, Search + arrows for Home/End",
+        rules: include_str!("presets/chromeos-parity.rules"),
+    },
+    Preset {
+        name: "mac-like",
+        description: "Search acts as Cmd (Search + C copies), media keys on the top row",
+        rules: include_str!("presets/mac-like.rules"),
+    },
+    Preset {
+        name: "plain-fkeys",
+        description: "top row always sends F keys, nothing else remapped",
+        rules: include_str!("presets/plain-fkeys.rules"),
+    },
+    Preset {
+        name: "minimal",
+        description: "F keys on the top row (media with Ctrl) and Lock as Windows + L",
+        rules: include_str!("presets/minimal.rules"),
+    },
+];
+
+pub fn find_preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name.eq_ignore_ascii_case(name))
+}
+
+/// The config of the preset called `name`.
+pub fn preset(name: &str) -> Result<Config> {
+    find_preset(name).map(Preset::config).ok_or_else(|| Error::UnknownPreset { name: name.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_preset_compiles() {
+        for preset in PRESETS {
+            let config = dsl::compile(preset.rules).unwrap_or_else(|e| panic!("{}: {}", preset.name, e));
+            config.validate().unwrap_or_else(|e| panic!("{}: {}", preset.name, e));
+            assert_eq!(find_preset(preset.name), Some(preset));
+        }
+    }
+
+    #[test]
+    fn no_preset_releases_search_first() {
+        for preset in PRESETS {
+            for rule in &preset.config().entries {
+                assert!(!rule.releases_search_first(), "{}: {:?}", preset.name, rule);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_presets_are_errors() {
+        assert!(matches!(preset("nonexistent"), Err(Error::UnknownPreset { .. })));
+        assert!(preset("Mac-Like").is_ok());
+    }
+}
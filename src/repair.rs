@@ -0,0 +1,304 @@
+//! Salvages what it can from a truncated or inconsistent settings file.
+//!
+//! A failed install typically leaves a file whose `remappings` count promises
+//! more entries than were written, ending in a partial entry or zero fill.
+//! `repair` keeps every complete entry, drops the partial tail, flags entries
+//! that cannot be real remaps and rewrites the header count to match.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::config::{
+    Config, KeyState, Modifier, RemapCfgKey, RemapOverride, Rule, CFG_MAGIC, CFG_SIZE, HEADER_SIZE,
+    MAX_ENTRIES,
+};
+use crate::error::{Error, Result};
+use crate::keys::{KEY_BREAK, KEY_E0, KEY_E1};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RepairOptions {
+    /// Keep entries that look like garbage instead of dropping them.
+    pub keep_suspect: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepairIssue {
+    /// Entry index in the damaged file, or `None` for the header and layout.
+    pub entry: Option<usize>,
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RepairIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.entry {
+            Some(i) => write!(f, "config {} (offset 0x{:04X}): {}", i, self.offset, self.message),
+            None => write!(f, "offset 0x{:04X}: {}", self.offset, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RepairReport {
+    pub file_size: usize,
+    pub declared_entries: u32,
+    /// Complete entries found within the declared count.
+    pub recovered_entries: usize,
+    /// Entries written to the repaired file.
+    pub kept_entries: usize,
+    /// Indices of recovered entries left out as garbage.
+    pub dropped_entries: Vec<usize>,
+    /// Bytes of a partial entry, or beyond the declared entries, left out.
+    pub discarded_bytes: usize,
+    pub issues: Vec<RepairIssue>,
+}
+
+impl RepairReport {
+    /// Whether the file needed no changes.
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "File size: {} bytes", self.file_size)?;
+        writeln!(f, "Declared entries: {}", self.declared_entries)?;
+        writeln!(f, "Recovered entries: {}", self.recovered_entries)?;
+        writeln!(f, "Kept entries: {}", self.kept_entries)?;
+        if !self.dropped_entries.is_empty() {
+            let dropped: Vec<String> = self.dropped_entries.iter().map(|i| i.to_string()).collect();
+            writeln!(f, "Dropped entries: {}", dropped.join(", "))?;
+        }
+        if self.discarded_bytes > 0 {
+            writeln!(f, "Discarded bytes: {}", self.discarded_bytes)?;
+        }
+        for issue in &self.issues {
+            writeln!(f, "  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Recovers a config from damaged file contents. Fails only if the header
+/// itself is missing or the magic number is wrong.
+pub fn repair(data: &[u8], options: RepairOptions) -> Result<(Config, RepairReport)> {
+    if data.len() < HEADER_SIZE {
+        return Err(Error::TruncatedHeader { len: data.len() });
+    }
+    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    if magic != CFG_MAGIC {
+        return Err(Error::BadMagic { found: magic });
+    }
+
+    let declared = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let available = (data.len() - HEADER_SIZE) / CFG_SIZE;
+    let recovered = (declared as usize).min(available).min(MAX_ENTRIES);
+
+    let mut report = RepairReport {
+        file_size: data.len(),
+        declared_entries: declared,
+        recovered_entries: recovered,
+        ..Default::default()
+    };
+    let header_issue = |offset: usize, message: String| RepairIssue { entry: None, offset, message };
+
+    // decode the complete entries under a corrected count
+    let end = HEADER_SIZE + recovered * CFG_SIZE;
+    report.discarded_bytes = data.len() - end;
+    if report.discarded_bytes > 0 {
+        report.issues.push(header_issue(end, format!("{} bytes after the last complete entry dropped", report.discarded_bytes)));
+    }
+    let mut bytes = data[..end].to_vec();
+    bytes[4..8].copy_from_slice(&(recovered as u32).to_le_bytes());
+    let mut config = Config::from_bytes(&bytes)?;
+
+    let header = &mut config.header;
+    if let Some(byte) = header.flip_byte.take() {
+        report.issues.push(header_issue(8, format!("flip_search_and_assistant_on_pixelbook byte {} reset to 1", byte)));
+    }
+    for (field, value, offset) in [
+        ("has_assistant_key", &mut header.has_assistant_key, 0x0009),
+        ("is_non_chrome_ec", &mut header.is_non_chrome_ec, 0x000D),
+    ] {
+        if let RemapOverride::Unknown(raw) = *value {
+            *value = RemapOverride::AutoDetect;
+            report.issues.push(header_issue(offset, format!("{} value {} reset to AutoDetect", field, raw)));
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (i, rule) in config.entries.into_iter().enumerate() {
+        let offset = HEADER_SIZE + i * CFG_SIZE;
+        let problems = suspect_fields(&rule);
+        if problems.is_empty() {
+            entries.push(rule);
+            continue;
+        }
+        let keep = options.keep_suspect;
+        report.issues.push(RepairIssue {
+            entry: Some(i),
+            offset,
+            message: format!("{}; {}", problems.join(", "), if keep { "kept" } else { "dropped" }),
+        });
+        if keep {
+            entries.push(rule);
+        } else {
+            report.dropped_entries.push(i);
+        }
+    }
+
+    report.kept_entries = entries.len();
+    if entries.len() as u32 != declared {
+        let message = if declared as usize > available {
+            format!(
+                "header declares {} entries, but the file holds only {} complete entries; count rewritten to {}",
+                declared,
+                available,
+                entries.len()
+            )
+        } else {
+            format!("header declares {} entries; count rewritten to {}", declared, entries.len())
+        };
+        report.issues.insert(0, header_issue(4, message));
+    }
+    config.header.remappings = entries.len() as u32;
+    config.entries = entries;
+    Ok((config, report))
+}
+
+// reasons an entry cannot be a real remap
+fn suspect_fields(rule: &Rule) -> Vec<String> {
+    let mut problems = Vec::new();
+    for modifier in Modifier::ALL {
+        if let KeyState::Unknown(value) = rule.modifier(modifier) {
+            problems.push(format!("{} is {}", modifier.field_name(), value));
+        }
+    }
+    if rule.original_key.make_code == 0 {
+        problems.push("original_key is empty".to_string());
+    }
+    if let Some(byte) = rule.fn_keys_byte {
+        problems.push(format!("remap_vivaldi_to_fn_keys is {}", byte));
+    }
+
+    let keys = [("original_key", &rule.original_key), ("remapped_key", &rule.remapped_key)];
+    let slots = rule.additional_keys.iter().map(|key| ("additional_keys", key));
+    for (field, key) in keys.into_iter().chain(slots) {
+        if let Some(problem) = bad_key(*key) {
+            problems.push(format!("{} {}", field, problem));
+        }
+    }
+    problems
+}
+
+fn bad_key(key: RemapCfgKey) -> Option<String> {
+    let RemapCfgKey { make_code, flags } = key;
+    if make_code > 0xFF {
+        Some(format!("has make code 0x{:04X}, beyond one byte", make_code))
+    } else if make_code & 0x80 != 0 {
+        // set 1 make codes stop at 0x7F; 0x80 is the break bit of a raw scan code
+        Some(format!("has make code 0x{:02X}, a break code", make_code))
+    } else if flags & !(KEY_BREAK | KEY_E0 | KEY_E1) != 0 {
+        Some(format!("has unknown flag bits 0x{:04X}", flags))
+    } else if flags & KEY_E0 != 0 && flags & KEY_E1 != 0 {
+        Some("has both E0 and E1 set".to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo::demo_config;
+
+    fn entry_offset(entry: usize) -> usize {
+        HEADER_SIZE + entry * CFG_SIZE
+    }
+
+    #[test]
+    fn clean_file_is_unchanged() {
+        let data = demo_config();
+        let (config, report) = repair(&data, RepairOptions::default()).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.kept_entries, 40);
+        assert_eq!(config.to_bytes(), data);
+    }
+
+    #[test]
+    fn truncated_file_keeps_the_complete_entries() {
+        let data = demo_config();
+        let cut = entry_offset(10) + 20;
+        let (config, report) = repair(&data[..cut], RepairOptions::default()).unwrap();
+        assert_eq!(report.declared_entries, 40);
+        assert_eq!(report.recovered_entries, 10);
+        assert_eq!(report.discarded_bytes, 20);
+        assert_eq!(config.header.remappings, 10);
+        assert_eq!(config.to_bytes(), {
+            let mut expected = data[..entry_offset(10)].to_vec();
+            expected[4..8].copy_from_slice(&10u32.to_le_bytes());
+            expected
+        });
+        assert_eq!(report.issues[0].offset, 4);
+        assert!(report.issues[0].message.contains("only 10 complete entries"), "{}", report.issues[0].message);
+    }
+
+    #[test]
+    fn zero_filled_entries_are_dropped() {
+        let mut data = demo_config();
+        data.resize(entry_offset(42), 0);
+        data[4..8].copy_from_slice(&42u32.to_le_bytes());
+        let (config, report) = repair(&data, RepairOptions::default()).unwrap();
+        assert_eq!(report.dropped_entries, [40, 41]);
+        assert_eq!(config.to_bytes(), demo_config());
+
+        let (config, report) = repair(&data, RepairOptions { keep_suspect: true }).unwrap();
+        assert!(report.dropped_entries.is_empty());
+        assert_eq!(config.entries.len(), 42);
+        assert!(report.issues.iter().any(|issue| issue.entry == Some(41) && issue.message.ends_with("kept")));
+    }
+
+    #[test]
+    fn make_codes_with_the_break_bit_are_suspect() {
+        let mut data = demo_config();
+        // remapped_key of entry 5
+        let offset = entry_offset(5) + 0x25;
+        data[offset..offset + 2].copy_from_slice(&0x9Du16.to_le_bytes());
+        let (config, report) = repair(&data, RepairOptions::default()).unwrap();
+        assert_eq!(report.dropped_entries, [5]);
+        assert_eq!(config.entries.len(), 39);
+        let issue = report.issues.iter().find(|issue| issue.entry == Some(5)).unwrap();
+        assert_eq!(issue.offset, entry_offset(5));
+        assert!(issue.message.contains("remapped_key has make code 0x9D, a break code"), "{}", issue.message);
+    }
+
+    #[test]
+    fn unknown_values_are_reset_or_flagged() {
+        let mut data = demo_config();
+        data[8] = 3;
+        data[0x09..0x0D].copy_from_slice(&9i32.to_le_bytes());
+        let offset = entry_offset(0) + 4;
+        data[offset..offset + 4].copy_from_slice(&4i32.to_le_bytes());
+        let (config, report) = repair(&data, RepairOptions::default()).unwrap();
+        assert_eq!(config.header.flip_byte, None);
+        assert_eq!(config.header.has_assistant_key, RemapOverride::AutoDetect);
+        assert_eq!(report.dropped_entries, [0]);
+        assert!(report.issues.iter().any(|issue| issue.message.contains("left_alt is 4")));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn damaged_header_cannot_be_repaired() {
+        let data = demo_config();
+        assert!(matches!(repair(&data[..12], RepairOptions::default()), Err(Error::TruncatedHeader { len: 12 })));
+        let mut data = data;
+        data[0] = b'X';
+        assert!(matches!(repair(&data, RepairOptions::default()), Err(Error::BadMagic { .. })));
+    }
+}
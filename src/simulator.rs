@@ -0,0 +1,271 @@
+//! Model of how the CrosKeyboard4 driver rewrites scan code events.
+//!
+//! Events are `RemapCfgKey` values: a make code plus `KEY_E0`/`KEY_E1` for the
+//! prefix and `KEY_BREAK` for a release. For every event the simulator
+//!
+//! 1. updates the physical modifier state if the event is a modifier key,
+//! 2. on a make, looks up the first entry whose original key and modifier
+//!    conditions match, and remembers it until the matching break,
+//! 3. on the first make, injects the entry's additional keys in slot order
+//!    (up to the first empty slot), then sends the output key: the function
+//!    key for a Vivaldi key when `remap_vivaldi_to_fn_keys` is set, otherwise
+//!    `remapped_key`, otherwise the original key,
+//! 4. on the break, releases the output key and undoes the additional keys
+//!    in reverse order. Releases are re-pressed only if that modifier is still
+//!    physically held, so `Ctrl + Left => release LCtrl + Home` leaves Ctrl
+//!    down for the next key.
+//!
+//! Typematic repeats of a held key repeat only the output key. The Pixelbook
+//! search/assistant flip is board-specific and not modelled.
+
+use std::collections::HashMap;
+
+use crate::config::{key_identity, Config, Modifier, ModifierSet, RemapCfgKey, Rule};
+use crate::error::{Error, Result};
+use crate::keys::*;
+
+/// A scan code event: make code, E0/E1 prefix and the break bit.
+pub type KeyEvent = RemapCfgKey;
+
+/// Classic Chromebook top row, F1 through F10, used when no board layout is known.
+pub const DEFAULT_TOP_ROW: [u16; 10] = [
+    VIVALDI_BACK,
+    VIVALDI_FWD,
+    VIVALDI_REFRESH,
+    VIVALDI_FULLSCREEN,
+    VIVALDI_OVERVIEW,
+    VIVALDI_BRIGHTNESS_DN,
+    VIVALDI_BRIGHTNESS_UP,
+    VIVALDI_MUTE,
+    VIVALDI_VOL_DN,
+    VIVALDI_VOL_UP,
+];
+
+pub fn is_break(event: KeyEvent) -> bool {
+    event.flags & KEY_BREAK != 0
+}
+
+fn with_break(key: RemapCfgKey, release: bool) -> KeyEvent {
+    let flags = if release { key.flags | KEY_BREAK } else { key.flags & !KEY_BREAK };
+    RemapCfgKey::with_values(key.make_code, flags)
+}
+
+/// What a matched entry sends in place of the original key.
+pub fn output_key(rule: &Rule, top_row: &[RemapCfgKey], original: RemapCfgKey) -> RemapCfgKey {
+    if rule.remap_vivaldi_to_fn_keys {
+        let position = top_row.iter().position(|&k| key_identity(k) == key_identity(original));
+        if let Some(i) = position.filter(|&i| i < FUNCTION_KEYS.len()) {
+            return RemapCfgKey::with_values(FUNCTION_KEYS[i], 0);
+        }
+    }
+    if !rule.remapped_key.is_empty() {
+        return rule.remapped_key;
+    }
+    key_identity(original)
+}
+
+/// Additional keys the driver injects: slot order, stopping at the first empty slot.
+pub fn injected_keys(rule: &Rule) -> impl Iterator<Item = RemapCfgKey> + '_ {
+    rule.additional_keys().copied()
+}
+
+// a key that is currently held and was remapped on its make
+struct ActiveRemap {
+    entry: usize,
+    output: RemapCfgKey,
+}
+
+pub struct Simulator<'a> {
+    config: &'a Config,
+    top_row: Vec<RemapCfgKey>,
+    held: ModifierSet,
+    active: HashMap<RemapCfgKey, ActiveRemap>,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        let top_row = DEFAULT_TOP_ROW.iter().map(|&code| RemapCfgKey::with_values(code, KEY_E0)).collect();
+        Self::with_top_row(config, top_row)
+    }
+
+    /// A simulator for a board whose top row sends `top_row`, left to right.
+    pub fn with_top_row(config: &'a Config, top_row: Vec<RemapCfgKey>) -> Self {
+        Self { config, top_row, held: ModifierSet::EMPTY, active: HashMap::new() }
+    }
+
+    /// Physical modifiers currently held.
+    pub fn held(&self) -> ModifierSet {
+        self.held
+    }
+
+    /// Index of the entry applied to `key` while it is held, if any.
+    pub fn active_entry(&self, key: RemapCfgKey) -> Option<usize> {
+        self.active.get(&key_identity(key)).map(|remap| remap.entry)
+    }
+
+    /// Feeds one event to the driver model and returns what it sends on.
+    pub fn process(&mut self, event: KeyEvent) -> Vec<KeyEvent> {
+        let identity = key_identity(event);
+        let release = is_break(event);
+
+        if let Some(modifier) = Modifier::from_key(event) {
+            if release {
+                self.held.remove(modifier);
+            } else {
+                self.held.insert(modifier);
+            }
+        }
+
+        if release {
+            let Some(remap) = self.active.remove(&identity) else {
+                return vec![event];
+            };
+            let rule = &self.config.entries[remap.entry];
+            let mut out = vec![with_break(remap.output, true)];
+            let injected: Vec<RemapCfgKey> = injected_keys(rule).collect();
+            for key in injected.into_iter().rev() {
+                if is_break(key) {
+                    // re-press a released modifier only if it is still down
+                    if Modifier::from_key(key).is_none_or(|m| self.held.contains(m)) {
+                        out.push(with_break(key, false));
+                    }
+                } else {
+                    out.push(with_break(key, true));
+                }
+            }
+            return out;
+        }
+
+        // typematic repeat of a remapped key
+        if let Some(remap) = self.active.get(&identity) {
+            return vec![with_break(remap.output, false)];
+        }
+
+        let Some(entry) = self.config.first_match(event, self.held) else {
+            return vec![event];
+        };
+        let rule = &self.config.entries[entry];
+        let output = output_key(rule, &self.top_row, event);
+
+        let mut out: Vec<KeyEvent> = injected_keys(rule).collect();
+        out.push(with_break(output, false));
+        self.active.insert(identity, ActiveRemap { entry, output });
+        out
+    }
+
+    /// Feeds a sequence of events and collects everything sent on.
+    pub fn run(&mut self, events: &[KeyEvent]) -> Vec<KeyEvent> {
+        events.iter().flat_map(|&event| self.process(event)).collect()
+    }
+}
+
+/// Parses events written as `+Key` (make) or `-Key` (break), separated by
+/// commas or new lines, e.g. `+LCtrl, +Left, -Left, -LCtrl`. `#` starts a comment.
+pub fn parse_events(text: &str) -> Result<Vec<KeyEvent>> {
+    let mut events = Vec::new();
+    for (i, raw_line) in text.lines().enumerate() {
+        let line = raw_line.split('#').next().unwrap_or("");
+        for item in line.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (release, name) = match (item.strip_prefix('+'), item.strip_prefix('-')) {
+                (Some(name), _) => (false, name),
+                (_, Some(name)) => (true, name),
+                _ => {
+                    return Err(Error::Syntax {
+                        line: i + 1,
+                        message: format!("expected '+Key' or '-Key', got '{}'", item),
+                    });
+                }
+            };
+            let key = parse_key(name.trim())?;
+            events.push(with_break(key, release));
+        }
+    }
+    Ok(events)
+}
+
+pub fn format_event(event: KeyEvent) -> String {
+    let sign = if is_break(event) { '-' } else { '+' };
+    let key = with_break(event, false);
+    match format_key(key) {
+        Some(name) => format!("{}{}", sign, name),
+        None => format!("{}{}", sign, key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::compile;
+
+    fn run(config: &Config, events: &str) -> String {
+        let events = parse_events(events).unwrap();
+        let sent = Simulator::new(config).run(&events);
+        sent.into_iter().map(format_event).collect::<Vec<_>>().join(", ")
+    }
+
+    #[test]
+    fn first_matching_entry_wins() {
+        let config = compile("Ctrl + Left => release LCtrl + Home\nCtrl Shift + Left => End\n").unwrap();
+        let mut simulator = Simulator::new(&config);
+        simulator.run(&parse_events("+LCtrl, +LShift").unwrap());
+        let left = parse_key("Left").unwrap();
+        assert_eq!(simulator.process(left).last(), Some(&parse_key("Home").unwrap()));
+        assert_eq!(simulator.active_entry(left), Some(0));
+    }
+
+    #[test]
+    fn released_modifier_is_pressed_again_while_held() {
+        let config = compile("Ctrl + Left => release LCtrl + Home").unwrap();
+        assert_eq!(
+            run(&config, "+LCtrl, +Left, -Left, -LCtrl"),
+            "+LCtrl, -LCtrl, +Home, -Home, +LCtrl, -LCtrl"
+        );
+        // Ctrl let go first: nothing to press again
+        assert_eq!(run(&config, "+LCtrl, +Left, -LCtrl, -Left"), "+LCtrl, -LCtrl, +Home, -LCtrl, -Home");
+    }
+
+    #[test]
+    fn injected_presses_are_released_in_reverse() {
+        let config = compile("Lock => LWin + LShift + L").unwrap();
+        assert_eq!(run(&config, "+Lock, -Lock"), "+LWin, +LShift, +L, -L, -LShift, -LWin");
+    }
+
+    #[test]
+    fn typematic_repeat_sends_only_the_output_key() {
+        let config = compile("Ctrl + Left => release LCtrl + Home").unwrap();
+        assert_eq!(
+            run(&config, "+LCtrl, +Left, +Left, +Left, -Left"),
+            "+LCtrl, -LCtrl, +Home, +Home, +Home, -Home, +LCtrl"
+        );
+    }
+
+    #[test]
+    fn injection_stops_at_the_first_empty_slot() {
+        let mut config = compile("Lock => LWin + L").unwrap();
+        config.entries[0].additional_keys[2] = parse_key("LShift").unwrap();
+        assert_eq!(run(&config, "+Lock, -Lock"), "+LWin, +L, -L, -LWin");
+    }
+
+    #[test]
+    fn fn_entries_follow_the_top_row() {
+        let config = compile("Vivaldi.Back => Fn\nVivaldi.Snapshot => Fn\n").unwrap();
+        assert_eq!(run(&config, "+Vivaldi.Back, -Vivaldi.Back"), "+F1, -F1");
+        // not on the classic top row: the key is sent unchanged
+        assert_eq!(run(&config, "+Vivaldi.Snapshot"), "+Vivaldi.Snapshot");
+
+        let top_row = vec![parse_key("Vivaldi.Snapshot").unwrap(), parse_key("Vivaldi.Back").unwrap()];
+        let mut simulator = Simulator::with_top_row(&config, top_row);
+        assert_eq!(simulator.process(parse_key("Vivaldi.Back").unwrap()), [parse_key("F2").unwrap()]);
+    }
+
+    #[test]
+    fn unmatched_keys_pass_through() {
+        let config = compile("Ctrl + Left => Home").unwrap();
+        assert_eq!(run(&config, "+Left, -Left, +A"), "+Left, -Left, +A");
+    }
+
+    #[test]
+    fn events_need_a_sign() {
+        assert!(matches!(parse_events("+A,\nB"), Err(Error::Syntax { line: 2, .. })));
+    }
+}
@@ -0,0 +1,200 @@
+//! Board detection from a Linux sysfs tree.
+//!
+//! On ChromeOS and Linux, `atkbd` publishes the top row of a Vivaldi keyboard
+//! as `function_row_physmap` on its serio device, and the firmware names the
+//! board in the DMI product name. Only the PS/2 keyboard's physmap is read:
+//! it is the keyboard the Windows driver filters, and its codes are set 1
+//! scan codes. `hid-vivaldi` and `cros-ec-keyb` publish the same attribute
+//! with HID usages and matrix positions.
+//!
+//! The root defaults to `/sys` and can point at a copy of the relevant files.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::boards::{board, Board};
+use crate::config::{Config, RemapCfgKey};
+use crate::error::{Error, Result};
+use crate::keys::KEY_E0;
+
+pub const DEFAULT_SYSFS_ROOT: &str = "/sys";
+
+const SERIO_DEVICES: &str = "bus/serio/devices";
+const PHYSMAP: &str = "function_row_physmap";
+const DMI_ID: &str = "class/dmi/id";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedBoard {
+    /// DMI product name, e.g. `Eve`.
+    pub product_name: Option<String>,
+    /// DMI board name, which some firmware sets instead.
+    pub board_name: Option<String>,
+    /// The `function_row_physmap` file the top row was read from.
+    pub physmap: Option<PathBuf>,
+    /// The bundled board matching the DMI names, if any.
+    pub known: Option<Board>,
+    /// Top-row keys, left to right: from the physmap when there is one,
+    /// otherwise from the bundled board.
+    pub top_row: Vec<RemapCfgKey>,
+}
+
+impl DetectedBoard {
+    pub fn name(&self) -> &str {
+        match &self.known {
+            Some(board) => &board.name,
+            None => self.product_name.as_deref().or(self.board_name.as_deref()).unwrap_or("unknown"),
+        }
+    }
+
+    /// `base` adapted to the detected top row, as `Board::configure` does.
+    /// `has_assistant_key` is only set for a bundled board; otherwise the
+    /// driver keeps detecting it.
+    pub fn configure(&self, base: &Config) -> Result<Config> {
+        let board = Board {
+            name: self.name().to_string(),
+            has_assistant_key: self.known.as_ref().is_some_and(|board| board.has_assistant_key),
+            top_row: self.top_row.clone(),
+        };
+        let mut config = board.configure(base)?;
+        if self.known.is_none() {
+            config.header.has_assistant_key = base.header.has_assistant_key;
+        }
+        Ok(config)
+    }
+}
+
+/// Reads the DMI names and the keyboard's top row below `root`.
+pub fn detect_board(root: &Path) -> Result<DetectedBoard> {
+    let dmi = root.join(DMI_ID);
+    let product_name = read_attribute(&dmi.join("product_name"));
+    let board_name = read_attribute(&dmi.join("board_name"));
+    let known = [&product_name, &board_name].into_iter().flatten().find_map(|name| board(name).ok());
+
+    let physmap = find_physmap(root);
+    let top_row = match (&physmap, &known) {
+        (Some(path), _) => parse_physmap(&fs::read_to_string(path)?)?,
+        (None, Some(board)) => board.top_row.clone(),
+        (None, None) => {
+            return Err(Error::BoardNotDetected { root: root.to_path_buf(), product: product_name });
+        }
+    };
+
+    Ok(DetectedBoard { product_name, board_name, physmap, known, top_row })
+}
+
+/// The first serio device's `function_row_physmap`, in device order.
+pub fn find_physmap(root: &Path) -> Option<PathBuf> {
+    let mut devices: Vec<PathBuf> = fs::read_dir(root.join(SERIO_DEVICES))
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    devices.sort();
+    devices.into_iter().map(|device| device.join(PHYSMAP)).find(|path| path.is_file())
+}
+
+/// Parses a physmap such as `EA E7 91 92`: hex scan codes separated by
+/// spaces, with 0x80 set for E0-prefixed keys as `atkbd` numbers them.
+pub fn parse_physmap(text: &str) -> Result<Vec<RemapCfgKey>> {
+    text.split_whitespace()
+        .map(|word| {
+            let invalid = || Error::InvalidKey { text: word.to_string() };
+            let code = u16::from_str_radix(word.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+            match code {
+                0x00..=0x7F => Ok(RemapCfgKey::with_values(code, 0)),
+                0x80..=0xFF => Ok(RemapCfgKey::with_values(code & 0x7F, KEY_E0)),
+                _ => Err(invalid()),
+            }
+        })
+        .collect()
+}
+
+fn read_attribute(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RemapOverride;
+    use crate::keys::parse_key;
+    use crate::testing::TempDir;
+
+    fn write(root: &Path, relative: &str, contents: &str) {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn with_product(name: &str, product: &str) -> TempDir {
+        let root = TempDir::new(name);
+        write(root.path(), "class/dmi/id/product_name", &format!("{}\n", product));
+        root
+    }
+
+    #[test]
+    fn physmap_sets_e0_for_the_high_bit() {
+        assert_eq!(
+            parse_physmap("EA E7\n0x3B 91").unwrap(),
+            vec![
+                RemapCfgKey::with_values(0x6A, KEY_E0),
+                RemapCfgKey::with_values(0x67, KEY_E0),
+                RemapCfgKey::with_values(0x3B, 0),
+                RemapCfgKey::with_values(0x11, KEY_E0),
+            ]
+        );
+        for text in ["EA ZZ", "100", "-1"] {
+            assert!(matches!(parse_physmap(text), Err(Error::InvalidKey { .. })), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn physmap_wins_over_the_bundled_board() {
+        let root = with_product("sysfs-physmap", "Eve");
+        write(root.path(), "bus/serio/devices/serio1/function_row_physmap", "3B 3C\n");
+        write(root.path(), "bus/serio/devices/serio0/function_row_physmap", "EA E7\n");
+
+        let detected = detect_board(root.path()).unwrap();
+        assert_eq!(detected.physmap, Some(root.path().join("bus/serio/devices/serio0/function_row_physmap")));
+        assert_eq!(detected.top_row, vec![parse_key("Back").unwrap(), parse_key("Refresh").unwrap()]);
+        assert_eq!(detected.name(), "eve");
+    }
+
+    #[test]
+    fn dmi_name_falls_back_to_the_bundled_board() {
+        let root = with_product("sysfs-dmi", "Eve");
+        let detected = detect_board(root.path()).unwrap();
+        let eve = board("eve").unwrap();
+        assert_eq!(detected.physmap, None);
+        assert_eq!(detected.top_row, eve.top_row);
+        assert_eq!(detected.known, Some(eve));
+    }
+
+    #[test]
+    fn unknown_board_without_physmap_is_not_detected() {
+        let root = with_product("sysfs-unknown", "Nowhere");
+        match detect_board(root.path()) {
+            Err(Error::BoardNotDetected { product, .. }) => assert_eq!(product.as_deref(), Some("Nowhere")),
+            other => panic!("{:?}", other),
+        }
+        let empty = TempDir::new("sysfs-empty");
+        assert!(matches!(detect_board(empty.path()), Err(Error::BoardNotDetected { product: None, .. })));
+    }
+
+    #[test]
+    fn unknown_board_keeps_assistant_detection() {
+        let root = with_product("sysfs-configure", "Nowhere");
+        write(root.path(), "bus/serio/devices/serio0/function_row_physmap", "EA E7");
+        let detected = detect_board(root.path()).unwrap();
+        assert_eq!(detected.name(), "Nowhere");
+
+        let mut base = crate::presets::preset("default").unwrap();
+        base.header.has_assistant_key = RemapOverride::AutoDetect;
+        let config = detected.configure(&base).unwrap();
+        assert_eq!(config.header.has_assistant_key, RemapOverride::AutoDetect);
+
+        let eve = DetectedBoard { known: Some(board("eve").unwrap()), ..detected };
+        assert_eq!(eve.configure(&base).unwrap().header.has_assistant_key, RemapOverride::Enable);
+    }
+}
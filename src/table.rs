@@ -0,0 +1,149 @@
+//! Effective behaviour of a config for every original key under all 256
+//! modifier combinations.
+
+use serde::Serialize;
+
+use crate::config::{Config, ModifierSet, RemapCfgKey};
+use crate::error::Result;
+use crate::explain::{explain, explain_with_top_row, Explanation};
+use crate::simulator::format_event;
+
+/// One (original key, modifier mask) cell of the table.
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingRow {
+    pub key: String,
+    pub make_code: u16,
+    pub flags: u16,
+    /// Held modifiers as a `ModifierSet` bit mask.
+    pub mask: u8,
+    pub modifiers: String,
+    /// Entry that fires, or `None` when the key passes through.
+    pub entry: Option<usize>,
+    pub press: Vec<String>,
+    pub release: Vec<String>,
+}
+
+impl From<Explanation> for MappingRow {
+    fn from(explanation: Explanation) -> Self {
+        let key = explanation.key;
+        MappingRow {
+            key: key.to_string(),
+            make_code: key.make_code,
+            flags: key.flags,
+            mask: explanation.held.0,
+            modifiers: explanation.held.to_string(),
+            entry: explanation.entry,
+            press: explanation.press.into_iter().map(format_event).collect(),
+            release: explanation.release.into_iter().map(format_event).collect(),
+        }
+    }
+}
+
+/// Expands a config into one row per original key and modifier mask, keys in
+/// order of first appearance and masks in ascending order.
+pub fn mapping_table(config: &Config) -> Vec<MappingRow> {
+    build(config, |key, held| explain(config, key, held))
+}
+
+/// Like `mapping_table`, for a board whose top row sends `top_row`.
+pub fn mapping_table_with_top_row(config: &Config, top_row: &[RemapCfgKey]) -> Vec<MappingRow> {
+    build(config, |key, held| explain_with_top_row(config, top_row.to_vec(), key, held))
+}
+
+fn build(config: &Config, explain: impl Fn(RemapCfgKey, ModifierSet) -> Explanation) -> Vec<MappingRow> {
+    let mut rows = Vec::new();
+    for key in config.original_keys().into_iter().filter(|key| !key.is_empty()) {
+        for held in ModifierSet::all() {
+            rows.push(MappingRow::from(explain(key, held)));
+        }
+    }
+    rows
+}
+
+pub fn to_csv(rows: &[MappingRow]) -> String {
+    let mut csv = String::from("key,make_code,flags,mask,modifiers,entry,press,release\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},0x{:02X},0x{:04X},0x{:02X},{},{},{},{}\n",
+            csv_field(&row.key),
+            row.make_code,
+            row.flags,
+            row.mask,
+            csv_field(&row.modifiers),
+            row.entry.map(|i| i.to_string()).unwrap_or_default(),
+            csv_field(&row.press.join(" ")),
+            csv_field(&row.release.join(" ")),
+        ));
+    }
+    csv
+}
+
+pub fn to_json(rows: &[MappingRow]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(rows)?)
+}
+
+// quotes a field that would otherwise break the row
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsl::compile;
+    use crate::keys::parse_key;
+
+    #[test]
+    fn one_row_per_key_and_mask() {
+        let text = "Ctrl + Left => release LCtrl + Home\nCtrl + Right => End\nCtrl Shift + Left => A\n";
+        let config = compile(text).unwrap();
+        let rows = mapping_table(&config);
+        assert_eq!(rows.len(), 2 * 256);
+        assert_eq!(rows[0].key, "Left");
+        assert_eq!(rows[256].key, "Right");
+        assert!(rows[..256].iter().enumerate().all(|(mask, row)| row.mask as usize == mask));
+
+        let ctrl = &rows[ModifierSet::from_iter([crate::Modifier::LeftCtrl]).0 as usize];
+        assert_eq!(ctrl.modifiers, "LCtrl");
+        assert_eq!(ctrl.entry, Some(0));
+        assert_eq!(ctrl.press, ["-LCtrl", "+Home"]);
+        assert_eq!(ctrl.release, ["-Home", "+LCtrl"]);
+        assert_eq!(rows[0].entry, None);
+        assert_eq!(rows[0].press, ["+Left"]);
+    }
+
+    #[test]
+    fn csv_rows() {
+        let config = compile("Ctrl + Left => release LCtrl + Home").unwrap();
+        let csv = to_csv(&mapping_table(&config));
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("key,make_code,flags,mask,modifiers,entry,press,release"));
+        assert_eq!(lines.next(), Some("Left,0x4B,0x0002,0x00,none,,+Left,-Left"));
+        assert_eq!(lines.next(), Some("Left,0x4B,0x0002,0x01,LCtrl,0,-LCtrl +Home,-Home +LCtrl"));
+        assert_eq!(csv.lines().count(), 257);
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_commas() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("+Left"), "+Left");
+        assert_eq!(csv_field("\"x\",y"), "\"\"\"x\"\",y\"");
+    }
+
+    #[test]
+    fn json_rows() {
+        let config = compile("Vivaldi.Back => Fn").unwrap();
+        let top_row = [parse_key("Vivaldi.Refresh").unwrap(), parse_key("Vivaldi.Back").unwrap()];
+        let json = to_json(&mapping_table_with_top_row(&config, &top_row)).unwrap();
+        let rows: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(rows.as_array().unwrap().len(), 256);
+        assert_eq!(rows[0]["key"], "Vivaldi.Back");
+        assert_eq!(rows[0]["entry"], 0);
+        assert_eq!(rows[0]["press"], serde_json::json!(["+F2"]));
+    }
+}
@@ -0,0 +1,212 @@
+//! The command-line interface: argument checks, `-` for stdin and stdout,
+//! and exit codes.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const REFERENCE: &[u8] = include_bytes!("../croskbsettings.bin");
+const REFERENCE_JSON: &str = include_str!("../output.json");
+
+// a fresh directory below the system temporary directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("config-generator-cli-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    // writes `data` to `name` in the directory and returns its path as a string
+    fn file(&self, name: &str, data: &[u8]) -> String {
+        let path = self.0.join(name);
+        fs::write(&path, data).unwrap();
+        path.display().to_string()
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_config-generator"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // commands that do not read stdin may exit before taking it
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    child.wait_with_output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn usage_errors_exit_with_2() {
+    for (args, message) in [
+        (&[][..], "missing subcommand"),
+        (&["frobnicate"][..], "unknown subcommand 'frobnicate'"),
+        (&["decode"][..], "decode"),
+        (&["diff", "a.bin"][..], "a second config file"),
+        (&["lint", "a.bin", "--limit", "3"][..], "--limit"),
+        (&["equiv", "a.bin", "b.bin", "--limit", "many"][..], "invalid --limit 'many'"),
+    ] {
+        let output = run(args, b"");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr(&output).contains(message), "{:?}: {}", args, stderr(&output));
+        assert!(stderr(&output).contains("Usage:"), "{:?}", args);
+    }
+}
+
+#[test]
+fn output_option_is_rejected_where_nothing_is_written() {
+    let dir = TempDir::new("reject-output");
+    let out = dir.path().join("out").display().to_string();
+    for (args, message) in [
+        (vec!["lint", "a.bin"], "lint does not write a file"),
+        (vec!["diff", "a.bin", "b.bin"], "diff does not write a file"),
+        (vec!["equiv", "a.bin", "b.bin"], "equiv does not write a file"),
+        (vec!["boards"], "boards does not write a file"),
+        (vec!["preset"], "lists the presets"),
+    ] {
+        let args: Vec<&str> = args.into_iter().chain(["-o", &out]).collect();
+        let output = run(&args, b"");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr(&output).contains(message), "{:?}: {}", args, stderr(&output));
+    }
+    assert!(!dir.path().join("out").exists());
+}
+
+#[test]
+fn dash_reads_stdin_and_writes_stdout() {
+    let output = run(&["decode", "-"], REFERENCE);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).trim_end(), REFERENCE_JSON.trim_end());
+
+    let output = run(&["encode", "-", "-o", "-"], REFERENCE_JSON.as_bytes());
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, REFERENCE);
+}
+
+#[test]
+fn output_option_writes_the_file() {
+    let dir = TempDir::new("write-output");
+    let out = dir.path().join("default.bin");
+    let output = run(&["preset", "default", "-o", &out.display().to_string()], b"");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(output.stdout.is_empty());
+    assert_eq!(fs::read(&out).unwrap(), REFERENCE);
+}
+
+#[test]
+fn runtime_errors_exit_with_1() {
+    let dir = TempDir::new("missing-input");
+    let missing = dir.path().join("missing.bin").display().to_string();
+    let output = run(&["decode", &missing], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("could not read"));
+    assert!(!stderr(&output).contains("Usage:"));
+}
+
+#[test]
+fn diff_exits_with_1_when_the_files_differ() {
+    let dir = TempDir::new("diff");
+    let reference = dir.file("reference.bin", REFERENCE);
+    let mut changed = REFERENCE.to_vec();
+    changed.extend_from_slice(&[1, 2, 3]);
+    let changed = dir.file("changed.bin", &changed);
+
+    let output = run(&["diff", &reference, &reference], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["diff", &reference, &changed], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "header trailing_bytes_hex: (none) -> 010203\n");
+}
+
+#[test]
+fn equiv_exits_with_1_when_the_configs_differ() {
+    let dir = TempDir::new("equiv");
+    let reference = dir.file("reference.bin", REFERENCE);
+    let rules = dir.file("rules.txt", b"Ctrl + Left => release LCtrl + Home\n");
+    let json = dir.file("reference.json", REFERENCE_JSON.as_bytes());
+
+    let output = run(&["equiv", &reference, &json], b"");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("are equivalent"));
+
+    let output = run(&["equiv", &reference, &rules, "--limit", "1"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("... and "));
+}
+
+#[test]
+fn lint_exits_with_1_on_errors_only() {
+    let dir = TempDir::new("lint");
+    let overlap = dir.file("overlap.txt", b"Ctrl + Left => Home\nShift + Left => End\n");
+    let shadowed = dir.file("shadowed.txt", b"Left => Home\nCtrl + Left => End\n");
+
+    let output = run(&["lint", &overlap], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("warning: config 1:"));
+
+    let output = run(&["lint", &shadowed], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).starts_with("error: config 1:"));
+}
+
+#[test]
+fn help_prints_usage_to_stdout() {
+    for args in [&["help"][..], &["decode", "--help"][..]] {
+        let output = run(args, b"");
+        assert!(output.status.success());
+        assert!(stdout(&output).starts_with("Usage:"));
+    }
+}
+
+#[test]
+fn install_shows_the_config_it_replaces() {
+    let dir = TempDir::new("install");
+    let drivers = dir.path().join("root/windows/SYSTEM32/Drivers");
+    fs::create_dir_all(&drivers).unwrap();
+    let root = dir.path().join("root").display().to_string();
+    let config = dir.file("minimal.bin", &run(&["preset", "minimal"], b"").stdout);
+    let install = || run(&["install", &config, "--root", &root, "--yes"], b"");
+
+    let output = install();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Installed config at"));
+    assert_eq!(fs::read(drivers.join("croskbsettings.bin")).unwrap(), fs::read(&config).unwrap());
+
+    let output = install();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stderr(&output);
+    assert!(text.contains(&format!("Installed config at {}:", drivers.join("croskbsettings.bin").display())), "{}", text);
+    assert!(text.contains("  21 entries, flip_search_and_assistant_on_pixelbook = true"));
+    assert!(text.contains("   20: Search + Lock => L\n"));
+
+    fs::write(drivers.join("croskbsettings.bin"), b"junk").unwrap();
+    let output = install();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("  could not be decoded: "));
+    assert_eq!(fs::read(drivers.join("croskbsettings.bin")).unwrap(), fs::read(&config).unwrap());
+}
@@ -0,0 +1,51 @@
+//! The generated output must match the reference file written by the C++
+//! VivaldiKeyboardTester byte for byte.
+
+use config_generator::{demo_config, default_config, dsl, preset, Config};
+
+const REFERENCE: &[u8] = include_bytes!("../croskbsettings.bin");
+const REFERENCE_JSON: &str = include_str!("../output.json");
+
+#[test]
+fn demo_config_matches_reference() {
+    assert_eq!(demo_config(), REFERENCE);
+}
+
+#[test]
+fn default_preset_matches_reference() {
+    assert_eq!(preset("default").unwrap().to_bytes(), REFERENCE);
+    assert_eq!(default_config().encoded_len(), REFERENCE.len());
+}
+
+#[test]
+fn rust_reference_matches_cpp_reference() {
+    assert_eq!(include_bytes!("../croskbsettingsrs.bin").as_slice(), REFERENCE);
+}
+
+#[test]
+fn binary_round_trip() {
+    assert_eq!(Config::from_bytes(REFERENCE).unwrap().to_bytes(), REFERENCE);
+}
+
+#[test]
+fn json_round_trip() {
+    let json = Config::from_bytes(REFERENCE).unwrap().to_json().unwrap();
+    assert_eq!(Config::from_json(&json).unwrap().to_bytes(), REFERENCE);
+}
+
+#[test]
+fn reference_json_encodes_to_reference() {
+    assert_eq!(Config::from_json(REFERENCE_JSON).unwrap().to_bytes(), REFERENCE);
+}
+
+#[test]
+fn reference_decodes_to_reference_json() {
+    let json = Config::from_bytes(REFERENCE).unwrap().to_json().unwrap();
+    assert_eq!(json.trim_end(), REFERENCE_JSON.trim_end());
+}
+
+#[test]
+fn rule_text_round_trip() {
+    let text = dsl::decompile(&Config::from_bytes(REFERENCE).unwrap());
+    assert_eq!(dsl::compile(&text).unwrap().to_bytes(), REFERENCE);
+}